@@ -5,6 +5,12 @@ declare license     "BSD";
 
 import("stdfaust.lib");
 
+// Number of chord voices (lead notes and guitar strings) and drone notes.
+// The application discovers these counts from the parameter paths,
+// changing them only requires to regenerate the DSP.
+voices = 4;
+drones = 4;
+
 // Precompute midi note to frequency
 // Clamped to the midi range since the dsp is not strict on that
 // It could be checked with the latest -ct 1 Faust option instead, but let's keep the dsp "correct".
@@ -16,19 +22,73 @@ with {
     cutoffFreq = note + cutoffNote : midikey2hz : si.smoo;
 };
 
-// Lead oscillator
-lead(pitchBend, res, cutoffNote) = os.sawtooth(f) * v : filter(res, note, cutoffNote)
-with {    
-    v = hslider("[1]volume", 0.0, 0, 1, 0.001) : si.smoo;
+// Envelope of a voice opened by its gate. The voice volume shapes it while
+// the gate is open, and is held during the release.
+envelope(attack, decay, sustain, release, gate, volume) = volume : si.smoo : ba.sAndH(gate) : _ * en.adsr(attack, decay, sustain, release, gate);
 
-    note = hslider("[0]note", 60, 0, 127, 0.001) + pitchBend;
-    f = note : midikey2hz : si.smoo;
+// Lead oscillator, blending the amounts of each waveform
+lead(sine, triangle, square, pulseWidth, saw, theremin, vibrato, vibratoOnset, glide, legato, attack, decay, sustain, release, pitchBend, res, cutoffNote) = wave * v : filter(res, note, cutoffNote)
+with {    
+    volume = hslider("[1]volume", 0.0, 0, 1, 0.001);
+    gate = checkbox("[2]gate");
+    v = envelope(attack, decay, sustain, release, gate, volume);
+
+    noteTarget = hslider("[0]note", 60, 0, 127, 0.001);
+    note = noteTarget + pitchBend;
+    // In legato, the silent voices follow their note right away so that
+    // only the connected notes slide
+    glideTime = select2(legato * (v < 0.001), glide, 0);
+    glidingNote = noteTarget : si.smooth(ba.tau2pole(glideTime)) : _ + pitchBend;
+    // The vibrato fades in once the voice plays, and is applied after the
+    // smoothing of the note so that it is not flattened
+    onset = gate : si.smooth(ba.tau2pole(vibratoOnset));
+    f = glidingNote : midikey2hz : si.smoo : _ * ba.semi2ratio(vibrato * onset);
     cutoffFreq = note + cutoffNote : midikey2hz : si.smoo;
+
+    wave = os.osc(f) * sine
+        + os.triangle(f) * triangle
+        + os.pulsetrain(f, pulseWidth) * square
+        + os.sawtooth(f) * saw
+        + thereminTone(f) * theremin;
+
+    // The heterodyne theremins mix two radio oscillators through a
+    // nonlinear stage, giving an asymmetric sine rich in even harmonics
+    thereminTone(f) = os.osc(f) : ef.cubicnl(0.4, 0.2) : fi.dcblocker;
 };
 
-leadChord(pitchBend, res, cutoffNote) = (pitchBend, res, cutoffNote) <: par(i, 4, vgroup("[3]%i", lead)) :> _ * v
+// The waveform amounts, the vibrato, the glide and the envelope are shared by
+// all the voices
+leadChord(pitchBend, res, cutoffNote) = (sine, triangle, square, pulseWidth, saw, theremin, vibrato, vibratoOnset, glide, legato, attack, decay, sustain, release, pitchBend, res, cutoffNote)
+    <: par(i, voices, vgroup("[3]%i", lead)) :> _ * v
 with {
     v = hslider("[0]volume", 0.0, 0, 1, 0.001) : si.smoo;
+
+    waveGroup(x) = vgroup("[4]wave", x);
+    sine = waveGroup(hslider("[0]sine", 0, 0, 1, 0.001)) : si.smoo;
+    triangle = waveGroup(hslider("[1]triangle", 0, 0, 1, 0.001)) : si.smoo;
+    square = waveGroup(hslider("[2]square", 0, 0, 1, 0.001)) : si.smoo;
+    pulseWidth = waveGroup(hslider("[3]pulse_width", 0.5, 0.05, 0.95, 0.001)) : si.smoo;
+    saw = waveGroup(hslider("[4]saw", 1, 0, 1, 0.001)) : si.smoo;
+    theremin = waveGroup(hslider("[5]theremin", 0, 0, 1, 0.001)) : si.smoo;
+
+    // Vibrato depth in semitones, onset in seconds
+    vibratoGroup(x) = vgroup("[5]vibrato", x);
+    vibratoRate = vibratoGroup(hslider("[0]rate", 5.5, 0.1, 12, 0.001));
+    vibratoDepth = vibratoGroup(hslider("[1]depth", 0, 0, 2, 0.001)) : si.smoo;
+    vibratoOnset = vibratoGroup(hslider("[2]onset", 0.3, 0, 2, 0.001));
+    vibrato = os.osc(vibratoRate) * vibratoDepth;
+
+    // Time constant of the slides between notes, in seconds
+    glideGroup(x) = vgroup("[6]glide", x);
+    glide = glideGroup(hslider("[0]time", 0, 0, 2, 0.001));
+    legato = glideGroup(checkbox("[1]legato"));
+
+    // Times in seconds, sustain level from 0 to 1
+    envelopeGroup(x) = vgroup("[7]envelope", x);
+    attack = envelopeGroup(hslider("[0]attack", 0.01, 0, 2, 0.001));
+    decay = envelopeGroup(hslider("[1]decay", 0.1, 0, 2, 0.001));
+    sustain = envelopeGroup(hslider("[2]sustain", 1, 0, 1, 0.001));
+    release = envelopeGroup(hslider("[3]release", 0.05, 0, 4, 0.001));
 };
 
 feedback(signal)= signal * 0.005;
@@ -38,16 +98,18 @@ elecGuitar(stringLength,pluckPosition,mute,gain,trigger) =
     (pm.elecGuitarModel(stringLength,pluckPosition,mute) : co.compressor_mono(20,-10,0,0.1)) * 1.5 ~
     (_  : ef.gate_mono(-20, 0.0001, 0.1, 0.02)) * 0.005 + pm.pluckString(stringLength,1,1,1,gain,trigger);
 
-guitarStrumNote(mute, pitchBend, res, cutoffNote) = elecGuitar(length,0.5,mute,0.5,gate)
+guitarStrumNote(mute, pitchBend, res, cutoffNote) = elecGuitar(length,0.5,mute,velocity,gate)
     : filter(res, note, cutoffNote)
 with {
     f = note + pitchBend : midikey2hz : si.smoo;
     length = f : pm.f2l;
     gate = button("[0]gate");
     note = hslider("[1]note", 80, 0, 127, 0.001);
+    velocity = hslider("[2]velocity", 0.5, 0, 1, 0.001);
 };
 
-guitarStrum(mute, pitchBend, res, cutoffNote) = (mute, pitchBend, res, cutoffNote) <: par(i, 5, vgroup("[3]%i", guitarStrumNote)) :> _;
+// One string per chord voice, plus the last one for the drone
+guitarStrum(mute, pitchBend, res, cutoffNote) = (mute, pitchBend, res, cutoffNote) <: par(i, voices + 1, vgroup("[3]%i", guitarStrumNote)) :> _;
 
 guitar(pitchBend, res, cutoffNote) = guitarStrum(mute, pitchBend, res, cutoffNote)
 with {
@@ -55,21 +117,66 @@ with {
 };
 
 // Drone
-droneNote(detune) = osc(note) + osc(note+detune) + osc(note-detune) : _ * volume
+droneNote(detune, tune, attack, decay, sustain, release) = osc(note) + osc(note+detune) + osc(note-detune) : _ * v
 with {
-    volume = hslider("[0]volume", 0, 0, 1, 0.001) : si.smoo;
-    note = hslider("[1]note", 60, 0, 127, 0.001);
+    volume = hslider("[0]volume", 0, 0, 1, 0.001);
+    note = hslider("[1]note", 60, 0, 127, 0.001) + tune;
+    gate = checkbox("[2]gate");
+    v = envelope(attack, decay, sustain, release, gate, volume);
     osc(note) = note : midikey2hz : si.smoo : os.triangle : _ / 3;
 };
 
-drone = detune <: par(i, 4, vgroup("[1]%i", droneNote)) :> _ : ef.cubicnl(drive, offset)
+drone(tune) = (detune, tune, attack, decay, sustain, release) <: par(i, drones, vgroup("[1]%i", droneNote)) :> _ : ef.cubicnl(drive, offset)
 with {
     detune = hslider("[0]detune", 0.1, 0, 0.3, 0.001);
     trumpet = hslider("[1]trumpet", 0, 0, 1, 0.001) : si.smoo;
+
+    envelopeGroup(x) = vgroup("[2]envelope", x);
+    attack = envelopeGroup(hslider("[0]attack", 0.05, 0, 4, 0.001));
+    decay = envelopeGroup(hslider("[1]decay", 0.1, 0, 2, 0.001));
+    sustain = envelopeGroup(hslider("[2]sustain", 1, 0, 1, 0.001));
+    release = envelopeGroup(hslider("[3]release", 0.3, 0, 8, 0.001));
     drive = trumpet / 3;
     offset = trumpet;
 };
 
+// Waveshaping distortion
+distortion(s) = s <: ef.cubicnl(drive, 0) * mix, s * (1-mix) :> _
+with {
+    mix = hslider("[0]mix", 0, 0, 1, 0.001) : si.smoo;
+    drive = hslider("[1]drive", 0.5, 0, 1, 0.001) : si.smoo;
+};
+
+// 3-band equalizer, gains in dB
+eq = fi.low_shelf(low, lowFreq) : fi.peak_eq(mid, midFreq, midFreq) : fi.high_shelf(high, highFreq)
+with {
+    low = hslider("[0]low", 0, -12, 12, 0.1) : si.smoo;
+    mid = hslider("[1]mid", 0, -12, 12, 0.1) : si.smoo;
+    high = hslider("[2]high", 0, -12, 12, 0.1) : si.smoo;
+    lowFreq = hslider("[3]low_freq[scale:log]", 200, 40, 1000, 1);
+    midFreq = hslider("[4]mid_freq[scale:log]", 1000, 200, 5000, 1);
+    highFreq = hslider("[5]high_freq[scale:log]", 4000, 1000, 16000, 1);
+};
+
+// Two delayed copies of the signal, modulated in opposite phases
+chorus(s) = s * (1-mix) + (voice(0) + voice(ma.PI)) / 2 * mix
+with {
+    mix = hslider("[0]mix", 0, 0, 1, 0.001) : si.smoo;
+    rate = hslider("[1]rate", 0.8, 0.05, 5, 0.001);
+    depth = hslider("[2]depth", 0.5, 0, 1, 0.001) : si.smoo;
+    // Delays in seconds
+    base = 0.02;
+    sweep = 0.005;
+    voice(phase) = s : de.fdelay(8192, ma.SR * (base + sweep * depth * os.oscp(rate, phase)));
+};
+
+phaser = pf.phaser2_mono(4, 0, 1000, 400, 1.5, 1500, rate, mix, feedback, 0)
+with {
+    mix = hslider("[0]mix", 0, 0, 1, 0.001) : si.smoo;
+    rate = hslider("[1]rate", 0.5, 0.05, 10, 0.001);
+    feedback = hslider("[2]feedback", 0.5, 0, 0.99, 0.001) : si.smoo;
+};
+
 echo(s) = s <: ef.echo(10.0, duration, feedback) * mix, s * (1-mix) :> _
 with {
     mix = hslider("[0]mix", 1.0, 0, 1, 0.001) : si.smoo;
@@ -88,12 +195,17 @@ with {
     modFreq = hslider("[6]mod_freq", 0.6, 0, 10, 0.001);
 };
 
-fx = vgroup("[0]echo", echo) : vgroup("[1]reverb", reverb);
+fx = vgroup("[2]distortion", distortion)
+    : vgroup("[3]eq", eq)
+    : vgroup("[4]chorus", chorus)
+    : vgroup("[5]phaser", phaser)
+    : vgroup("[0]echo", echo)
+    : vgroup("[1]reverb", reverb);
 
 // Mix
-process = hgroup("[2]drone", drone) * drone_volume
-    + vgroup("[0]lead", leadChord)(pitchBend, res, cutoffNote) * lead_volume
-    + hgroup("[1]pluck", guitar)(pitchBend, res, cutoffNote) * pluck_volume
+process = hgroup("[2]drone", drone)(tune) * drone_volume
+    + vgroup("[0]lead", leadChord)(pitchBend + tune, res, cutoffNote) * lead_volume
+    + hgroup("[1]pluck", guitar)(pitchBend + tune, res, cutoffNote) * pluck_volume
     : hgroup("[2]fx", fx)
     : co.compressor_mono(12,-4,800/1000000,0.5)
     : _ * master_volume
@@ -110,4 +222,8 @@ with {
     res = filterGroup(hslider("[2]res", 0, 0, 0.99, 0.001)) : si.smoo;
     
     pitchBend = hslider("[5]pitchBend", 0, -1, 1, 0.001) : si.smoo;
+
+    // Frequency of A4, as an offset in midi note from 440Hz
+    refPitch = hslider("[6]refPitch", 440, 415, 466, 0.1);
+    tune = refPitch : ba.hz2midikey : _ - 69 : si.smoo;
 };
\ No newline at end of file
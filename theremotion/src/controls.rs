@@ -13,16 +13,40 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct Controls {
     /// Lead voice chord
-    pub lead: [NoteControl; 4],
+    pub lead: Vec<NoteControl>,
     /// Global lead volume
     pub lead_volume: Control,
+    /// Amount of lead sine wave
+    pub wave_sine: Control,
+    /// Amount of lead triangle wave
+    pub wave_triangle: Control,
+    /// Amount of lead square wave
+    pub wave_square: Control,
+    /// Pulse width of the lead square wave
+    pub wave_pulse_width: Control,
+    /// Amount of lead sawtooth wave
+    pub wave_saw: Control,
+    /// Amount of lead theremin tone
+    pub wave_theremin: Control,
+    /// Lead vibrato frequency (Hz)
+    pub vibrato_rate: Control,
+    /// Lead vibrato depth (semitones)
+    pub vibrato_depth: Control,
+    /// Time for the lead vibrato to fade in (seconds)
+    pub vibrato_onset: Control,
+    /// Time constant of the lead slides between notes (seconds)
+    pub glide_time: Control,
+    /// Only slide between connected lead notes
+    pub glide_legato: BoolControl,
+    /// Amplitude envelope of the lead voices
+    pub lead_envelope: EnvelopeControl,
     /// Filter cutoff
     pub cutoff_note: Control,
     /// Filter resonance
     pub resonance: Control,
-    /// Guitar strum
-    pub strum: [PluckControl; 4],
-    /// Drone string (actually fifth pluck)
+    /// Guitar strum, one string per lead voice
+    pub strum: Vec<PluckControl>,
+    /// Drone string (the pluck after the chord strings)
     pub strum_drone: PluckControl,
     /// Guitar pluck damping
     pub pluck_mute: Control,
@@ -31,9 +55,13 @@ pub struct Controls {
     /// Drone "trumpet"
     pub drone_trumpet: Control,
     /// Drone notes
-    pub drone_notes: [NoteControl; 4],
+    pub drone_notes: Vec<NoteControl>,
+    /// Amplitude envelope of the drone notes
+    pub drone_envelope: EnvelopeControl,
     /// Global pitch bend (guitar+lead)
     pub pitch_bend: Control,
+    /// Frequency of A4 (Hz)
+    pub reference_pitch: Control,
 
     /// Echo amount
     pub echo_mix: Control,
@@ -56,14 +84,38 @@ pub struct Controls {
     /// Reverb room size
     pub reverb_size: Control,
 
-    /// Amount of early reverb diffusion
-    pub reverb_early_diff: Control,
+    /// Distortion amount
+    pub distortion_mix: Control,
 
-    /// Amount of the reverb modulation
-    pub reverb_mod_depth: Control,
+    /// Distortion drive (0-1)
+    pub distortion_drive: Control,
 
-    /// Frequency of the reverb modulation
-    pub reverb_mod_freq: Control,
+    /// Equalizer low band gain (dB)
+    pub eq_low: Control,
+
+    /// Equalizer mid band gain (dB)
+    pub eq_mid: Control,
+
+    /// Equalizer high band gain (dB)
+    pub eq_high: Control,
+
+    /// Chorus amount
+    pub chorus_mix: Control,
+
+    /// Chorus modulation frequency (Hz)
+    pub chorus_rate: Control,
+
+    /// Chorus modulation depth (0-1)
+    pub chorus_depth: Control,
+
+    /// Phaser amount
+    pub phaser_mix: Control,
+
+    /// Phaser sweep frequency (Hz)
+    pub phaser_rate: Control,
+
+    /// Phaser feedback (0-1)
+    pub phaser_feedback: Control,
 
     /// Master volume
     pub mix_master_volume: Control,
@@ -76,40 +128,48 @@ pub struct Controls {
 
     /// Guitar volume
     pub mix_pluck_volume: Control,
+
+    /// Other parameters, saved in the presets by path
+    pub parameters: Vec<Control>,
 }
 
 impl From<&StateHandle> for Controls {
     fn from(state: &StateHandle) -> Self {
-        Self {
-            lead: [0, 1, 2, 3].map(|i| {
-                (
-                    state.by_path(format!("lead/{i}/note").as_str()),
-                    state.by_path(format!("lead/{i}/volume").as_str()),
-                )
-                    .into()
-            }),
+        let lead: Vec<NoteControl> = state.voices("lead", ["note", "volume", "gate"]);
+        // The last string is the drone one
+        let mut strum: Vec<PluckControl> = state.voices("pluck", ["note", "gate", "velocity"]);
+        let strum_drone = strum.pop().expect("Missing the pluck drone string");
+        assert_eq!(
+            lead.len(),
+            strum.len(),
+            "The DSP should have one guitar string per lead voice"
+        );
+        let mut controls = Self {
+            lead,
             lead_volume: state.by_path("lead/volume").into(),
+            wave_sine: state.by_path("lead/wave/sine").into(),
+            wave_triangle: state.by_path("lead/wave/triangle").into(),
+            wave_square: state.by_path("lead/wave/square").into(),
+            wave_pulse_width: state.by_path("lead/wave/pulse_width").into(),
+            wave_saw: state.by_path("lead/wave/saw").into(),
+            wave_theremin: state.by_path("lead/wave/theremin").into(),
+            vibrato_rate: state.by_path("lead/vibrato/rate").into(),
+            vibrato_depth: state.by_path("lead/vibrato/depth").into(),
+            vibrato_onset: state.by_path("lead/vibrato/onset").into(),
+            glide_time: state.by_path("lead/glide/time").into(),
+            glide_legato: state.by_path("lead/glide/legato").into(),
+            lead_envelope: state.group("lead/envelope", ENVELOPE_PARAMS),
             cutoff_note: state.by_path("filter/cutoffNote").into(),
             resonance: state.by_path("filter/res").into(),
-            strum: [0, 1, 2, 3].map(|i| {
-                (
-                    state.by_path(format!("pluck/{i}/note").as_str()),
-                    state.by_path(format!("pluck/{i}/gate").as_str()),
-                )
-                    .into()
-            }),
-            strum_drone: (state.by_path("pluck/4/note"), state.by_path("pluck/4/gate")).into(),
+            strum,
+            strum_drone,
             pluck_mute: state.by_path("pluck/mute").into(),
             drone_detune: state.by_path("drone/detune").into(),
             drone_trumpet: state.by_path("drone/trumpet").into(),
-            drone_notes: [0, 1, 2, 3].map(|i| {
-                (
-                    state.by_path(format!("drone/{i}/note").as_str()),
-                    state.by_path(format!("drone/{i}/volume").as_str()),
-                )
-                    .into()
-            }),
+            drone_notes: state.voices("drone", ["note", "volume", "gate"]),
+            drone_envelope: state.group("drone/envelope", ENVELOPE_PARAMS),
             pitch_bend: state.by_path("pitchBend").into(),
+            reference_pitch: state.by_path("refPitch").into(),
             echo_mix: state.by_path("fx/echo/mix").into(),
             echo_duration: state.by_path("fx/echo/duration").into(),
             echo_feedback: state.by_path("fx/echo/feedback").into(),
@@ -117,14 +177,112 @@ impl From<&StateHandle> for Controls {
             reverb_time: state.by_path("fx/reverb/time").into(),
             reverb_damp: state.by_path("fx/reverb/damp").into(),
             reverb_size: state.by_path("fx/reverb/size").into(),
-            reverb_early_diff: state.by_path("fx/reverb/early_diff").into(),
-            reverb_mod_depth: state.by_path("fx/reverb/mod_depth").into(),
-            reverb_mod_freq: state.by_path("fx/reverb/mod_freq").into(),
+            distortion_mix: state.by_path("fx/distortion/mix").into(),
+            distortion_drive: state.by_path("fx/distortion/drive").into(),
+            eq_low: state.by_path("fx/eq/low").into(),
+            eq_mid: state.by_path("fx/eq/mid").into(),
+            eq_high: state.by_path("fx/eq/high").into(),
+            chorus_mix: state.by_path("fx/chorus/mix").into(),
+            chorus_rate: state.by_path("fx/chorus/rate").into(),
+            chorus_depth: state.by_path("fx/chorus/depth").into(),
+            phaser_mix: state.by_path("fx/phaser/mix").into(),
+            phaser_rate: state.by_path("fx/phaser/rate").into(),
+            phaser_feedback: state.by_path("fx/phaser/feedback").into(),
             mix_master_volume: state.by_path("mix/master").into(),
             mix_drone_volume: state.by_path("mix/drone").into(),
             mix_lead_volume: state.by_path("mix/lead").into(),
             mix_pluck_volume: state.by_path("mix/pluck").into(),
-        }
+            parameters: vec![],
+        };
+        let dedicated = controls.dedicated_paths();
+        controls.parameters = state
+            .params()
+            .iter()
+            .filter(|(_, node)| {
+                matches!(
+                    node.widget_type(),
+                    WidgetType::VerticalSlider(_)
+                        | WidgetType::HorizontalSlider(_)
+                        | WidgetType::NumEntry(_)
+                )
+            })
+            .filter(|(_, node)| !dedicated.contains(&node.path()))
+            .map(|(idx, node)| NodeIndex(*idx, node).into())
+            .collect();
+        controls
+    }
+}
+
+impl Controls {
+    /// Paths of the parameters played live or having their own preset
+    /// setting
+    fn dedicated_paths(&self) -> Vec<String> {
+        let notes = self
+            .lead
+            .iter()
+            .chain(&self.drone_notes)
+            .flat_map(|n| [&n.note, &n.volume]);
+        let strings = self
+            .strum
+            .iter()
+            .chain(std::iter::once(&self.strum_drone))
+            .flat_map(|s| [&s.note, &s.velocity]);
+        let envelopes = [&self.lead_envelope, &self.drone_envelope]
+            .into_iter()
+            .flat_map(|e| [&e.attack, &e.decay, &e.sustain, &e.release]);
+        let others = [
+            &self.lead_volume,
+            &self.wave_sine,
+            &self.wave_triangle,
+            &self.wave_square,
+            &self.wave_pulse_width,
+            &self.wave_saw,
+            &self.wave_theremin,
+            &self.vibrato_rate,
+            &self.vibrato_depth,
+            &self.vibrato_onset,
+            &self.glide_time,
+            &self.cutoff_note,
+            &self.resonance,
+            &self.pluck_mute,
+            &self.drone_detune,
+            &self.drone_trumpet,
+            &self.pitch_bend,
+            &self.reference_pitch,
+            &self.echo_mix,
+            &self.echo_duration,
+            &self.echo_feedback,
+            &self.reverb_mix,
+            &self.reverb_time,
+            &self.reverb_damp,
+            &self.reverb_size,
+            &self.distortion_mix,
+            &self.distortion_drive,
+            &self.eq_low,
+            &self.eq_mid,
+            &self.eq_high,
+            &self.chorus_mix,
+            &self.chorus_rate,
+            &self.chorus_depth,
+            &self.phaser_mix,
+            &self.phaser_rate,
+            &self.phaser_feedback,
+            &self.mix_master_volume,
+            &self.mix_drone_volume,
+            &self.mix_lead_volume,
+            &self.mix_pluck_volume,
+        ];
+        notes
+            .chain(strings)
+            .chain(envelopes)
+            .chain(others)
+            .map(|c| c.path.clone())
+            .collect()
+    }
+
+    /// Guitar string by index, the drone string being after the chord ones
+    pub fn string(&self, index: usize) -> &PluckControl {
+        self.strum.get(index).unwrap_or(&self.strum_drone)
     }
 }
 
@@ -139,6 +297,9 @@ pub struct Control {
 
     /// Parameter index in the DSP
     pub idx: i32,
+
+    /// The value is displayed on a logarithmic scale (`[scale:log]`)
+    pub log: bool,
 }
 
 impl Control {
@@ -173,10 +334,17 @@ impl From<NodeIndex<'_>> for Control {
                 nodeindex.1.path()
             ),
         };
+        let log = nodeindex
+            .1
+            .metadata()
+            .iter()
+            .any(|[key, value]| key == "scale" && value == "log");
         Self {
             input: input.clone(),
             path: nodeindex.1.path(),
             idx: nodeindex.0,
+            // A log scale needs positive values
+            log: log && *input.range.start() > 0.0,
         }
     }
 }
@@ -219,6 +387,9 @@ pub struct NoteControl {
 
     /// Control for the volume of the note
     pub volume: Control,
+
+    /// Control opening the amplitude envelope of the note
+    pub gate: BoolControl,
 }
 
 impl NoteControl {
@@ -232,11 +403,42 @@ impl NoteControl {
     }
 }
 
-impl From<(NodeIndex<'_>, NodeIndex<'_>)> for NoteControl {
-    fn from((note, volume): (NodeIndex<'_>, NodeIndex<'_>)) -> Self {
+impl From<[NodeIndex<'_>; 3]> for NoteControl {
+    fn from([note, volume, gate]: [NodeIndex<'_>; 3]) -> Self {
         Self {
             note: note.into(),
             volume: volume.into(),
+            gate: gate.into(),
+        }
+    }
+}
+
+/// Parameters of an envelope group, in the order of [`EnvelopeControl`]
+const ENVELOPE_PARAMS: [&str; 4] = ["attack", "decay", "sustain", "release"];
+
+/// Amplitude envelope (ADSR) input controls
+#[derive(Debug, Clone)]
+pub struct EnvelopeControl {
+    /// Time to reach the full level (seconds)
+    pub attack: Control,
+
+    /// Time to fall to the sustain level (seconds)
+    pub decay: Control,
+
+    /// Level held while the note plays (0-1)
+    pub sustain: Control,
+
+    /// Time to fade out once the note stops (seconds)
+    pub release: Control,
+}
+
+impl From<[NodeIndex<'_>; 4]> for EnvelopeControl {
+    fn from([attack, decay, sustain, release]: [NodeIndex<'_>; 4]) -> Self {
+        Self {
+            attack: attack.into(),
+            decay: decay.into(),
+            sustain: sustain.into(),
+            release: release.into(),
         }
     }
 }
@@ -249,6 +451,9 @@ pub struct PluckControl {
 
     /// Control for the pluck impulse
     pub pluck: BoolControl,
+
+    /// Control for the pluck strength
+    pub velocity: Control,
 }
 
 impl PluckControl {
@@ -260,13 +465,26 @@ impl PluckControl {
     ) -> Result<(), SendError<thread_dsp::Msg>> {
         self.note.send(dsp_tx, note.note())
     }
+
+    /// Pluck the string with the given velocity (0-1)
+    pub fn send_pluck(
+        &self,
+        dsp_tx: &Sender<thread_dsp::Msg>,
+        velocity: f32,
+    ) -> Result<(), SendError<thread_dsp::Msg>> {
+        self.velocity
+            .send(dsp_tx, self.velocity.get_scaled(velocity, &(0.0..=1.0)))?;
+        self.pluck.send(dsp_tx, true);
+        Ok(())
+    }
 }
 
-impl From<(NodeIndex<'_>, NodeIndex<'_>)> for PluckControl {
-    fn from((note, pluck): (NodeIndex<'_>, NodeIndex<'_>)) -> Self {
+impl From<[NodeIndex<'_>; 3]> for PluckControl {
+    fn from([note, pluck, velocity]: [NodeIndex<'_>; 3]) -> Self {
         Self {
             note: note.into(),
             pluck: pluck.into(),
+            velocity: velocity.into(),
         }
     }
 }
@@ -276,18 +494,47 @@ pub struct NodeIndex<'a>(i32, &'a Node);
 
 /// Ability to retrieve node by path
 trait NodeByPath {
+    /// From a faust path, get the node and its index, if it exists
+    fn try_by_path(&self, path: &str) -> Option<NodeIndex<'_>>;
+
     /// From a faust path, get the node and its index
-    fn by_path(&self, path: &str) -> NodeIndex<'_>;
+    fn by_path(&self, path: &str) -> NodeIndex<'_> {
+        self.try_by_path(path)
+            .unwrap_or_else(|| panic!("Wrongly parameterized parameter {path}"))
+    }
+
+    /// Get the given parameters of a group
+    fn group<T, const N: usize>(&self, group: &str, params: [&str; N]) -> T
+    where
+        T: for<'a> From<[NodeIndex<'a>; N]>,
+    {
+        T::from(params.map(|param| self.by_path(format!("{group}/{param}").as_str())))
+    }
+
+    /// Discover the numbered voices of a group (`{group}/0`, `{group}/1`...),
+    /// each of them made of the given parameters
+    fn voices<T, const N: usize>(&self, group: &str, params: [&str; N]) -> Vec<T>
+    where
+        T: for<'a> From<[NodeIndex<'a>; N]>,
+    {
+        (0..)
+            .map_while(|i| {
+                let nodes: Vec<NodeIndex<'_>> = params
+                    .iter()
+                    .map(|param| self.try_by_path(format!("{group}/{i}/{param}").as_str()))
+                    .collect::<Option<_>>()?;
+                Some(T::from(nodes.try_into().ok()?))
+            })
+            .collect()
+    }
 }
 
 impl NodeByPath for StateHandle {
-    fn by_path(&self, path: &str) -> NodeIndex<'_> {
-        for n in self.params().iter() {
-            if n.1.path() == path {
-                return NodeIndex(*n.0, n.1);
-            }
-        }
-        panic!("Wrongly parameterized parameter {path}");
+    fn try_by_path(&self, path: &str) -> Option<NodeIndex<'_>> {
+        self.params()
+            .iter()
+            .find(|n| n.1.path() == path)
+            .map(|n| NodeIndex(*n.0, n.1))
     }
 }
 
@@ -303,3 +550,27 @@ pub fn convert_range(
     let out_max = *output_range.end();
     (((value - in_min) * (out_max - out_min)) / (in_max - in_min)) + out_min
 }
+
+#[cfg(test)]
+mod tests {
+    use faust_state::DspHandle;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn dsp_controls_test() {
+        // Fails when the generated dsp.rs is out of date with instrument.dsp
+        let dsp = theremotion_dsp::Instrument::default_boxed();
+        let (_dsp, state) = DspHandle::<theremotion_dsp::Instrument>::from_dsp(dsp);
+        let controls = Controls::from(&state);
+        assert!(!controls.lead.is_empty());
+        assert_eq!(controls.lead.len(), controls.strum.len());
+        assert!(!controls.drone_notes.is_empty());
+        let dedicated = controls.dedicated_paths();
+        assert!(controls
+            .parameters
+            .iter()
+            .all(|c| !dedicated.contains(&c.path)));
+    }
+}
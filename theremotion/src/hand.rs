@@ -45,6 +45,11 @@ impl HandMessage {
         )
     }
 
+    /// Forward tilt of the palm (rad)
+    pub fn tilt(&self) -> f32 {
+        -self.rotation.euler_angles().0
+    }
+
     pub fn rotation_from_body(&self) -> Option<f32> {
         let angle = -self.rotation.euler_angles().2 * self.x_factor();
         if angle < PI && angle > -HALF_PI {
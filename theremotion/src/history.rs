@@ -0,0 +1,127 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Number of states kept to be undone
+const HISTORY_SIZE: usize = 50;
+
+/// Changes closer than this are undone together, so that moving a knob
+/// doesn't fill the history
+const HISTORY_MERGE: Duration = Duration::from_millis(500);
+
+/// Bounded undo and redo stacks of snapshots
+#[derive(Debug)]
+pub struct History<T> {
+    undo: VecDeque<T>,
+    redo: Vec<T>,
+    /// Time of the last recorded change
+    last_record: Option<Instant>,
+}
+
+impl<T> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            last_record: None,
+        }
+    }
+}
+
+impl<T> History<T> {
+    /// Remember the state before a change
+    pub fn record(&mut self, previous: T, now: Instant) {
+        let merged = self
+            .last_record
+            .map(|last| now < last + HISTORY_MERGE)
+            .unwrap_or(false);
+        self.last_record = Some(now);
+        self.redo.clear();
+        if merged && !self.undo.is_empty() {
+            return;
+        }
+        self.push_undo(previous);
+    }
+
+    /// Push a state to be undone, forgetting the oldest one when full
+    fn push_undo(&mut self, state: T) {
+        if self.undo.len() == HISTORY_SIZE {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(state);
+    }
+
+    /// State before the last change, if any
+    pub fn undo(&mut self, current: T) -> Option<T> {
+        let previous = self.undo.pop_back()?;
+        self.redo.push(current);
+        self.last_record = None;
+        Some(previous)
+    }
+
+    /// State before the last undo, if any
+    pub fn redo(&mut self, current: T) -> Option<T> {
+        let next = self.redo.pop()?;
+        self.push_undo(current);
+        self.last_record = None;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn undo_redo_test() {
+        let start = Instant::now();
+        let mut history = History::default();
+        history.record(1, start);
+        history.record(2, start + Duration::from_secs(1));
+        assert_eq!(Some(2), history.undo(3));
+        assert_eq!(Some(1), history.undo(2));
+        assert_eq!(None, history.undo(1));
+        assert_eq!(Some(2), history.redo(1));
+        // A new change drops the redo stack
+        history.record(2, start + Duration::from_secs(2));
+        assert_eq!(None, history.redo(4));
+        assert_eq!(Some(2), history.undo(4));
+    }
+
+    #[rstest]
+    fn merge_test() {
+        let start = Instant::now();
+        let mut history = History::default();
+        history.record(1, start);
+        history.record(2, start + Duration::from_millis(100));
+        history.record(3, start + Duration::from_millis(200));
+        assert_eq!(Some(1), history.undo(4));
+        assert_eq!(None, history.undo(1));
+    }
+
+    #[rstest]
+    fn bounded_test() {
+        let start = Instant::now();
+        let mut history = History::default();
+        for i in 0..HISTORY_SIZE + 10 {
+            history.record(i, start + Duration::from_secs(i as u64));
+        }
+        let mut current = HISTORY_SIZE + 10;
+        let mut count = 0;
+        while let Some(previous) = history.undo(current) {
+            current = previous;
+            count += 1;
+        }
+        assert_eq!(HISTORY_SIZE, count);
+        assert_eq!(10, current);
+
+        // Redoing everything keeps the bound
+        while let Some(next) = history.redo(current) {
+            current = next;
+        }
+        assert_eq!(HISTORY_SIZE, history.undo.len());
+    }
+}
@@ -0,0 +1,378 @@
+/// Maximum number of layers kept separately, older layers are merged
+pub const MAX_LAYERS: usize = 8;
+
+/// Command sent to the looper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LooperCommand {
+    /// Record the first loop, or overdub a new layer
+    Record,
+    /// Start or stop the playback
+    PlayStop,
+    /// Remove the last layer
+    Undo,
+    /// Remove all the layers
+    Clear,
+}
+
+/// What the looper is currently doing
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LooperState {
+    /// Nothing recorded
+    #[default]
+    Empty,
+    /// Recording the first layer, defining the loop length
+    Recording,
+    /// Playing the loop
+    Playing,
+    /// Playing the loop and recording a new layer
+    Overdubbing,
+    /// Loop recorded but not playing
+    Stopped,
+}
+
+/// State of the looper, without the audio.
+///
+/// This is shared by the audio looper and the conductor mirroring it.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LooperStatus {
+    /// Current looper state
+    pub state: LooperState,
+    /// Number of recorded layers
+    pub layers: usize,
+}
+
+impl LooperStatus {
+    /// Apply a command
+    pub fn apply(&mut self, command: LooperCommand) {
+        use LooperState::*;
+        match (command, self.state) {
+            (LooperCommand::Record, Empty) => {
+                self.state = Recording;
+                self.layers = 1;
+            }
+            (LooperCommand::Record, Recording | Overdubbing) => self.state = Playing,
+            (LooperCommand::Record, Playing | Stopped) => {
+                self.state = Overdubbing;
+                self.layers = (self.layers + 1).min(MAX_LAYERS);
+            }
+            (LooperCommand::PlayStop, Empty) => {}
+            (LooperCommand::PlayStop, Recording | Stopped) => self.state = Playing,
+            (LooperCommand::PlayStop, Playing | Overdubbing) => self.state = Stopped,
+            (LooperCommand::Undo, Empty | Recording) => *self = Self::default(),
+            (LooperCommand::Undo, Playing | Overdubbing | Stopped) => {
+                if self.layers <= 1 {
+                    *self = Self::default();
+                } else {
+                    self.layers -= 1;
+                    if self.state == Overdubbing {
+                        self.state = Playing;
+                    }
+                }
+            }
+            (LooperCommand::Clear, _) => *self = Self::default(),
+        }
+    }
+}
+
+/// Allocator of the looper buffers, out of the audio callback.
+///
+/// It mirrors the status of the audio looper to provide a new buffer along
+/// with the commands needing more layers than ever recorded before.
+pub struct LooperAllocator {
+    /// Mirrored looper status
+    status: LooperStatus,
+    /// Number of buffers already provided
+    allocated: usize,
+    /// Length of the buffers, in samples
+    max_length: usize,
+}
+
+impl LooperAllocator {
+    /// Creates a new [`LooperAllocator`] for loops up to `max_length` samples.
+    pub fn new(max_length: usize) -> Self {
+        Self {
+            status: LooperStatus::default(),
+            allocated: 0,
+            max_length,
+        }
+    }
+
+    /// Buffer to give to the looper along with the command, if needed
+    pub fn buffer_for(&mut self, command: LooperCommand) -> Option<Vec<f32>> {
+        self.status.apply(command);
+        if self.status.layers <= self.allocated {
+            return None;
+        }
+        self.allocated += 1;
+        Some(vec![0.0; self.max_length])
+    }
+}
+
+/// Audio looper, recording and playing back a mono signal.
+///
+/// The commands and the processing run in the audio callback, so the layer
+/// buffers are provided by a [`LooperAllocator`] and reused.
+pub struct Looper {
+    /// Current state
+    status: LooperStatus,
+    /// Pool of layer buffers, each holding the maximum loop length
+    buffers: Vec<Vec<f32>>,
+    /// Buffers of the recorded layers, from the oldest one. The last one is
+    /// being recorded when overdubbing
+    layers: Vec<usize>,
+    /// Samples recorded in the first layer
+    recorded: usize,
+    /// Loop length in samples, defined by the first layer
+    length: usize,
+    /// Playback position in samples
+    position: usize,
+}
+
+impl Default for Looper {
+    fn default() -> Self {
+        Self {
+            status: LooperStatus::default(),
+            buffers: Vec::with_capacity(MAX_LAYERS),
+            layers: Vec::with_capacity(MAX_LAYERS),
+            recorded: 0,
+            length: 0,
+            position: 0,
+        }
+    }
+}
+
+impl Looper {
+    /// Add a buffer from the [`LooperAllocator`] to the pool
+    pub fn add_buffer(&mut self, buffer: Vec<f32>) {
+        self.buffers.push(buffer);
+    }
+
+    /// A buffer not used by any layer
+    fn free_buffer(&self) -> usize {
+        (0..self.buffers.len())
+            .find(|b| !self.layers.contains(b))
+            .expect("No free looper buffer")
+    }
+
+    /// Apply a command
+    pub fn command(&mut self, command: LooperCommand) {
+        let previous = self.status.state;
+        self.status.apply(command);
+        let state = self.status.state;
+
+        match (previous, state) {
+            (_, LooperState::Empty) => {
+                self.layers.clear();
+                self.recorded = 0;
+                self.length = 0;
+                self.position = 0;
+            }
+            (LooperState::Empty, LooperState::Recording) => {
+                let buffer = self.free_buffer();
+                self.layers.push(buffer);
+            }
+            (LooperState::Recording, _) => {
+                self.length = self.recorded;
+                self.position = 0;
+            }
+            (LooperState::Playing | LooperState::Stopped, LooperState::Overdubbing) => {
+                if self.layers.len() == MAX_LAYERS {
+                    // Merge the two oldest layers to make room
+                    let first = self.layers[0];
+                    let second = self.layers.remove(1);
+                    let merged = std::mem::take(&mut self.buffers[second]);
+                    for (a, b) in self.buffers[first].iter_mut().zip(&merged[..self.length]) {
+                        *a += b;
+                    }
+                    self.buffers[second] = merged;
+                }
+                let buffer = self.free_buffer();
+                self.buffers[buffer][..self.length].fill(0.0);
+                self.layers.push(buffer);
+            }
+            _ => {}
+        }
+
+        if command == LooperCommand::Undo && self.layers.len() > self.status.layers {
+            self.layers.pop();
+        }
+        if state == LooperState::Stopped {
+            self.position = 0;
+        }
+    }
+
+    /// Record and play the loop in place
+    pub fn process(&mut self, buffer: &mut [f32]) {
+        match self.status.state {
+            LooperState::Empty | LooperState::Stopped => {}
+            LooperState::Recording => {
+                let layer = &mut self.buffers[self.layers[0]];
+                let count = buffer.len().min(layer.len() - self.recorded);
+                layer[self.recorded..self.recorded + count].copy_from_slice(&buffer[..count]);
+                self.recorded += count;
+            }
+            LooperState::Playing | LooperState::Overdubbing => {
+                if self.length == 0 {
+                    return;
+                }
+                let overdub = self.status.state == LooperState::Overdubbing;
+                let played = if overdub {
+                    self.layers.len() - 1
+                } else {
+                    self.layers.len()
+                };
+                for sample in buffer.iter_mut() {
+                    let input = *sample;
+                    for layer in &self.layers[..played] {
+                        *sample += self.buffers[*layer][self.position];
+                    }
+                    if overdub {
+                        if let Some(layer) = self.layers.last() {
+                            self.buffers[*layer][self.position] += input;
+                        }
+                    }
+                    self.position = (self.position + 1) % self.length;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Looper with its allocator, as driven by the DSP thread
+    struct TestLooper {
+        looper: Looper,
+        allocator: LooperAllocator,
+    }
+
+    impl TestLooper {
+        fn new(max_length: usize) -> Self {
+            Self {
+                looper: Looper::default(),
+                allocator: LooperAllocator::new(max_length),
+            }
+        }
+
+        fn command(&mut self, command: LooperCommand) {
+            if let Some(buffer) = self.allocator.buffer_for(command) {
+                self.looper.add_buffer(buffer);
+            }
+            self.looper.command(command);
+        }
+
+        fn process(&mut self, buffer: &mut [f32]) {
+            self.looper.process(buffer);
+        }
+    }
+
+    #[rstest]
+    #[case(vec![], LooperState::Empty, 0)]
+    #[case(vec![LooperCommand::Record], LooperState::Recording, 1)]
+    #[case(vec![LooperCommand::Record, LooperCommand::Record], LooperState::Playing, 1)]
+    #[case(vec![LooperCommand::Record, LooperCommand::PlayStop, LooperCommand::Record], LooperState::Overdubbing, 2)]
+    #[case(vec![LooperCommand::Record, LooperCommand::Record, LooperCommand::Record, LooperCommand::Undo], LooperState::Playing, 1)]
+    #[case(vec![LooperCommand::Record, LooperCommand::Record, LooperCommand::Undo], LooperState::Empty, 0)]
+    #[case(vec![LooperCommand::Record, LooperCommand::Record, LooperCommand::PlayStop], LooperState::Stopped, 1)]
+    #[case(vec![LooperCommand::Record, LooperCommand::Record, LooperCommand::Clear], LooperState::Empty, 0)]
+    fn status_test(
+        #[case] commands: Vec<LooperCommand>,
+        #[case] state: LooperState,
+        #[case] layers: usize,
+    ) {
+        let mut status = LooperStatus::default();
+        for command in commands {
+            status.apply(command);
+        }
+        assert_eq!(LooperStatus { state, layers }, status);
+    }
+
+    #[rstest]
+    fn overdub_test() {
+        let mut looper = TestLooper::new(10);
+        looper.command(LooperCommand::Record);
+        looper.process(&mut [1.0, 2.0, 3.0]);
+        looper.command(LooperCommand::Record);
+
+        // Playback
+        let mut buffer = [0.0; 4];
+        looper.process(&mut buffer);
+        assert_eq!([1.0, 2.0, 3.0, 1.0], buffer);
+
+        // Overdub a layer
+        looper.command(LooperCommand::Record);
+        let mut buffer = [10.0; 3];
+        looper.process(&mut buffer);
+        assert_eq!([12.0, 13.0, 11.0], buffer);
+        looper.command(LooperCommand::Record);
+        let mut buffer = [0.0; 3];
+        looper.process(&mut buffer);
+        assert_eq!([12.0, 13.0, 11.0], buffer);
+
+        // Undo the overdub
+        looper.command(LooperCommand::Undo);
+        let mut buffer = [0.0; 3];
+        looper.process(&mut buffer);
+        assert_eq!([2.0, 3.0, 1.0], buffer);
+        assert_eq!(1, looper.looper.status.layers);
+    }
+
+    #[rstest]
+    fn merge_test() {
+        let mut looper = TestLooper::new(2);
+        looper.command(LooperCommand::Record);
+        looper.process(&mut [1.0, 1.0]);
+        looper.command(LooperCommand::Record);
+        for _ in 0..MAX_LAYERS {
+            looper.command(LooperCommand::Record);
+            looper.process(&mut [1.0, 1.0]);
+            looper.command(LooperCommand::Record);
+        }
+        assert_eq!(MAX_LAYERS, looper.looper.status.layers);
+
+        // The merged layers are still played
+        let mut buffer = [0.0; 2];
+        looper.process(&mut buffer);
+        assert_eq!([MAX_LAYERS as f32 + 1.0; 2], buffer);
+
+        // Undoing reuses the buffer of the last layer
+        looper.command(LooperCommand::Undo);
+        looper.command(LooperCommand::Record);
+        let mut buffer = [0.0; 2];
+        looper.process(&mut buffer);
+        assert_eq!([MAX_LAYERS as f32; 2], buffer);
+    }
+
+    #[rstest]
+    fn max_length_test() {
+        let mut looper = TestLooper::new(2);
+        looper.command(LooperCommand::Record);
+        looper.process(&mut [1.0, 2.0, 3.0]);
+        looper.command(LooperCommand::Record);
+        let mut buffer = [0.0; 3];
+        looper.process(&mut buffer);
+        assert_eq!([1.0, 2.0, 1.0], buffer);
+    }
+
+    #[rstest]
+    fn allocation_test() {
+        let mut allocator = LooperAllocator::new(4);
+        assert!(allocator.buffer_for(LooperCommand::PlayStop).is_none());
+        assert_eq!(
+            Some(4),
+            allocator.buffer_for(LooperCommand::Record).map(|b| b.len())
+        );
+        assert!(allocator.buffer_for(LooperCommand::Record).is_none());
+        assert!(allocator.buffer_for(LooperCommand::Record).is_some());
+
+        // The buffers of the removed layers are reused
+        assert!(allocator.buffer_for(LooperCommand::Undo).is_none());
+        assert!(allocator.buffer_for(LooperCommand::Record).is_none());
+        assert!(allocator.buffer_for(LooperCommand::Clear).is_none());
+        assert!(allocator.buffer_for(LooperCommand::Record).is_none());
+    }
+}
@@ -17,15 +17,39 @@ mod thread_dsp;
 /// Thread reading the hand positions
 mod thread_leap;
 
+/// Thread listening to the MIDI inputs
+mod thread_midi;
+
+/// Thread writing the settings to the disk
+mod thread_saver;
+
 /// Mod creating the main window and event loop
 mod thread_ui;
 
 /// Application settings
 mod settings;
 
+/// Audio looper recording the master output
+mod looper;
+
+/// Strum engine scheduling the guitar plucks
+mod strum;
+
+/// Smooth transitions between presets
+mod morph;
+
+/// Vibrato depth driven by the hand gestures
+mod vibrato;
+
+/// Undo and redo of the settings changes
+mod history;
+
 /// Music related types and algorithms
 mod solfege;
 
+/// Tempo clock shared by the rhythmic features
+mod tempo;
+
 /// Newtypes for strongly typed exchanges
 mod types;
 
@@ -37,7 +61,7 @@ pub use types::*;
 
 use default_boxed::DefaultBoxed;
 use faust_state::DspHandle;
-use settings::Settings;
+use settings::{Settings, SettingsLocation};
 use theremotion_ui::*;
 
 /// Theremotion version
@@ -48,7 +72,15 @@ fn main() {
     tracing_subscriber::fmt::init();
 
     // Read application settings
-    let settings = Settings::read();
+    let settings_path = settings_path();
+    log::info!("Using the settings {}", settings_path.display());
+    let (mut settings, storage_error) = Settings::read(&settings_path);
+    let preset_errors = settings.load_directory_presets();
+    for error in &preset_errors {
+        log::error!("Failed to load the preset {}", error);
+    }
+    let preset_errors = (!preset_errors.is_empty())
+        .then(|| format!("{} preset files could not be loaded.", preset_errors.len()));
 
     if settings.system.high_priority_process {
         set_high_priority();
@@ -58,13 +90,33 @@ fn main() {
     let (ui_tx, ui_rx) = std::sync::mpsc::channel(); // Messages to update the UI
     let (dsp_tx, dsp_rx) = std::sync::mpsc::channel(); // Messages to update the DSP parameters
     let (leap_tx, leap_rx) = std::sync::mpsc::channel(); // Messages to leap thread
+    let (midi_tx, midi_rx) = std::sync::mpsc::channel(); // Messages to MIDI thread
     let (co_tx, co_rx) = std::sync::mpsc::channel(); // Messages to the conductor thread
+    let (saver_tx, saver_rx) = std::sync::mpsc::channel(); // Settings to save
 
     // Init DSP and its controls metadata
     let dsp = theremotion_dsp::Instrument::default_boxed();
     let (dsp, state) = DspHandle::<theremotion_dsp::Instrument>::from_dsp(dsp);
     let controls = controls::Controls::from(&state);
 
+    // Correct the settings out of the DSP ranges
+    let corrections = settings.validate(&settings::Limits::from(&controls));
+    for correction in &corrections {
+        log::warn!("Corrected setting: {}", correction);
+    }
+    let corrections = (!corrections.is_empty())
+        .then(|| format!("{} invalid settings were corrected.", corrections.len()));
+    let storage_error = [storage_error, preset_errors, corrections]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join("\n");
+    if !storage_error.is_empty() {
+        ui_tx
+            .send(thread_ui::Msg::StorageError(storage_error))
+            .unwrap();
+    }
+
     // Set the DSP in its initial state
     settings
         .current_preset
@@ -79,6 +131,17 @@ fn main() {
         dsp_tx.clone(),
         ui_tx.clone(),
         leap_tx.clone(),
+        midi_tx.clone(),
+        saver_tx,
+    );
+
+    // Init settings saving
+    let saver = thread_saver::run(
+        saver_rx,
+        ui_tx.clone(),
+        co_tx.clone(),
+        settings_path,
+        settings.system.presets_directory.clone(),
     );
 
     // Init sound output
@@ -87,6 +150,9 @@ fn main() {
     // Init leap thread
     let leap = thread_leap::run(co_tx.clone(), leap_rx);
 
+    // Init MIDI thread
+    let midi = thread_midi::run(co_tx.clone(), midi_rx);
+
     // Start UI
     let (window, _window_timer) = thread_ui::run(co_tx.clone(), ui_rx, controls.clone(), settings);
     window.run().expect("Failed to start the UI");
@@ -98,6 +164,25 @@ fn main() {
     dsp.join().expect("Error when stopping the DSP thread");
 
     leap.join().expect("Error when stopping the leap worker");
+
+    midi.join().expect("Error when stopping the MIDI thread");
+
+    saver.join().expect("Error when stopping the saver thread");
+}
+
+/// Settings file chosen from the command line and the environment
+fn settings_path() -> std::path::PathBuf {
+    let location = SettingsLocation::from_args(
+        std::env::args().skip(1),
+        std::env::var(settings::CONFIG_ENV).ok(),
+    );
+    match location.and_then(|location| location.path()) {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, settings::USAGE);
+            std::process::exit(2);
+        }
+    }
 }
 
 #[cfg(target_os = "windows")]
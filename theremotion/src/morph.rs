@@ -0,0 +1,384 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    settings::{
+        ChorusSettings, DistortionSettings, EnvelopeSettings, EqSettings, MixSettings,
+        PhaserSettings, Preset, ReverbSettings, WaveSettings,
+    },
+    Volume,
+};
+
+/// Interval between two updates of a morph
+const MORPH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Closing of the hand completing a gesture morph
+const GESTURE_END: f32 = 0.95;
+
+/// Linear interpolation
+pub trait Lerp {
+    /// Value at `t` (0-1) between `self` and `to`
+    fn lerp(&self, to: &Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+impl Lerp for Volume {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Volume(self.0.lerp(&to.0, t))
+    }
+}
+
+impl Lerp for MixSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            master: self.master.lerp(&to.master, t),
+            lead: self.lead.lerp(&to.lead, t),
+            guitar: self.guitar.lerp(&to.guitar, t),
+            drone: self.drone.lerp(&to.drone, t),
+        }
+    }
+}
+
+impl Lerp for WaveSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            sine: self.sine.lerp(&to.sine, t),
+            triangle: self.triangle.lerp(&to.triangle, t),
+            square: self.square.lerp(&to.square, t),
+            pulse_width: self.pulse_width.lerp(&to.pulse_width, t),
+            saw: self.saw.lerp(&to.saw, t),
+            theremin: self.theremin.lerp(&to.theremin, t),
+        }
+    }
+}
+
+impl Lerp for EnvelopeSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            attack: self.attack.lerp(&to.attack, t),
+            decay: self.decay.lerp(&to.decay, t),
+            sustain: self.sustain.lerp(&to.sustain, t),
+            release: self.release.lerp(&to.release, t),
+        }
+    }
+}
+
+impl Lerp for ReverbSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            mix: self.mix.lerp(&to.mix, t),
+            time: self.time.lerp(&to.time, t),
+            damp: self.damp.lerp(&to.damp, t),
+            size: self.size.lerp(&to.size, t),
+        }
+    }
+}
+
+impl Lerp for DistortionSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            mix: self.mix.lerp(&to.mix, t),
+            drive: self.drive.lerp(&to.drive, t),
+        }
+    }
+}
+
+impl Lerp for EqSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            low: self.low.lerp(&to.low, t),
+            mid: self.mid.lerp(&to.mid, t),
+            high: self.high.lerp(&to.high, t),
+        }
+    }
+}
+
+impl Lerp for ChorusSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            mix: self.mix.lerp(&to.mix, t),
+            rate: self.rate.lerp(&to.rate, t),
+            depth: self.depth.lerp(&to.depth, t),
+        }
+    }
+}
+
+impl Lerp for PhaserSettings {
+    fn lerp(&self, to: &Self, t: f32) -> Self {
+        Self {
+            mix: self.mix.lerp(&to.mix, t),
+            rate: self.rate.lerp(&to.rate, t),
+            feedback: self.feedback.lerp(&to.feedback, t),
+        }
+    }
+}
+
+/// How a preset change is played
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MorphMode {
+    /// Switch instantly
+    #[default]
+    Off,
+    /// Morph over the morph duration
+    Timed,
+    /// Morph following the closing of the volume hand
+    Gesture,
+}
+
+/// Morph settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MorphSettings {
+    /// How the preset changes are played
+    pub mode: MorphMode,
+    /// Duration of a timed morph (seconds)
+    pub duration: f32,
+    /// Position (0-1) at which the discrete values switch
+    pub switch_at: f32,
+}
+
+impl Default for MorphSettings {
+    fn default() -> Self {
+        Self {
+            mode: MorphMode::Off,
+            duration: 4.0,
+            switch_at: 0.5,
+        }
+    }
+}
+
+/// Preset at `t` (0-1) between two presets.
+///
+/// The continuous values are interpolated, the discrete ones (scale, root,
+/// octaves...) switch at `switch_at`. The parameters set in only one of the
+/// presets switch too, going back to their default value when missing.
+pub fn morph_presets(from: &Preset, to: &Preset, t: f32, switch_at: f32) -> Preset {
+    let t = t.clamp(0.0, 1.0);
+    let mut preset = if t < switch_at {
+        from.clone()
+    } else {
+        to.clone()
+    };
+    preset.wave = from.wave.lerp(&to.wave, t);
+    preset.vibrato.rate = from.vibrato.rate.lerp(&to.vibrato.rate, t);
+    preset.vibrato.depth = from.vibrato.depth.lerp(&to.vibrato.depth, t);
+    preset.vibrato.onset = from.vibrato.onset.lerp(&to.vibrato.onset, t);
+    preset.glide.time = from.glide.time.lerp(&to.glide.time, t);
+    preset.lead_envelope = from.lead_envelope.lerp(&to.lead_envelope, t);
+    preset.drone_envelope = from.drone_envelope.lerp(&to.drone_envelope, t);
+    preset.mix = from.mix.lerp(&to.mix, t);
+    preset.fx.echo.mix = from.fx.echo.mix.lerp(&to.fx.echo.mix, t);
+    preset.fx.echo.duration = from.fx.echo.duration.lerp(&to.fx.echo.duration, t);
+    preset.fx.echo.feedback = from.fx.echo.feedback.lerp(&to.fx.echo.feedback, t);
+    preset.fx.reverb = from.fx.reverb.lerp(&to.fx.reverb, t);
+    preset.fx.distortion = from.fx.distortion.lerp(&to.fx.distortion, t);
+    preset.fx.eq = from.fx.eq.lerp(&to.fx.eq, t);
+    preset.fx.chorus = from.fx.chorus.lerp(&to.fx.chorus, t);
+    preset.fx.phaser = from.fx.phaser.lerp(&to.fx.phaser, t);
+    preset.drone.detune = from.drone.detune.lerp(&to.drone.detune, t);
+    preset.reference_pitch = from.reference_pitch.lerp(&to.reference_pitch, t);
+    for (path, value) in preset.parameters.iter_mut() {
+        if let (Some(from), Some(to)) = (from.parameters.get(path), to.parameters.get(path)) {
+            *value = from.lerp(to, t);
+        }
+    }
+    preset
+}
+
+/// Morph in progress between two presets
+#[derive(Debug)]
+pub struct Morph {
+    from: Preset,
+    to: Preset,
+    settings: MorphSettings,
+    /// Start of the morph
+    start: Instant,
+    /// Current position (0-1)
+    position: f32,
+    /// Last time the morph was applied
+    last_update: Option<Instant>,
+    /// Position of the last applied preset
+    applied: Option<f32>,
+}
+
+impl Morph {
+    /// Creates a new [`Morph`] starting now.
+    pub fn new(from: Preset, to: Preset, settings: MorphSettings, now: Instant) -> Self {
+        Self {
+            from,
+            to,
+            settings,
+            start: now,
+            position: 0.0,
+            last_update: None,
+            applied: None,
+        }
+    }
+
+    /// Follow the closing of the hand (0-1) in gesture mode
+    pub fn gesture(&mut self, grab: f32) {
+        if self.settings.mode == MorphMode::Gesture {
+            // The morph can't go back
+            self.position = self.position.max((grab / GESTURE_END).clamp(0.0, 1.0));
+        }
+    }
+
+    /// Get the morphed preset if it moved and it is time to apply it
+    pub fn update(&mut self, now: Instant) -> Option<Preset> {
+        if self.settings.mode == MorphMode::Timed {
+            let duration = self.settings.duration.max(0.0);
+            self.position = if duration > 0.0 {
+                (now.duration_since(self.start).as_secs_f32() / duration).min(1.0)
+            } else {
+                1.0
+            };
+        }
+        if self.applied == Some(self.position) {
+            return None;
+        }
+        let due = self
+            .last_update
+            .map(|last| now >= last + MORPH_INTERVAL)
+            .unwrap_or(true);
+        if !due && !self.is_done() {
+            return None;
+        }
+        self.last_update = Some(now);
+        self.applied = Some(self.position);
+        Some(morph_presets(
+            &self.from,
+            &self.to,
+            self.position,
+            self.settings.switch_at,
+        ))
+    }
+
+    /// Instant at which the morph is due to be applied again, if it can move
+    /// without a gesture
+    pub fn next_update(&self) -> Option<Instant> {
+        if self.settings.mode != MorphMode::Timed && self.applied == Some(self.position) {
+            return None;
+        }
+        let next = self
+            .last_update
+            .map(|last| last + MORPH_INTERVAL)
+            .unwrap_or(self.start);
+        Some(next)
+    }
+
+    /// The target preset is reached
+    pub fn is_done(&self) -> bool {
+        self.position >= 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use staff::Pitch;
+
+    use super::*;
+
+    fn presets() -> (Preset, Preset) {
+        let from = Preset {
+            pitch: Pitch::C,
+            ..Default::default()
+        };
+        let mut to = Preset {
+            pitch: Pitch::D,
+            ..Default::default()
+        };
+        to.mix.master = Volume(0.0);
+        to.drone.detune = 0.2;
+        to.parameters.insert("fx/reverb/mod_freq".to_string(), 4.0);
+        (from, to)
+    }
+
+    #[rstest]
+    #[case(0.0, Pitch::C, 1.0)]
+    #[case(0.25, Pitch::C, 0.75)]
+    #[case(0.75, Pitch::D, 0.25)]
+    #[case(2.0, Pitch::D, 0.0)]
+    fn morph_presets_test(#[case] t: f32, #[case] pitch: Pitch, #[case] master: f32) {
+        let (from, to) = presets();
+        let preset = morph_presets(&from, &to, t, 0.5);
+        assert_eq!(pitch, preset.pitch);
+        assert!((preset.mix.master.0 - master).abs() < 0.001);
+    }
+
+    #[rstest]
+    fn morph_parameters_test() {
+        let (mut from, to) = presets();
+        from.parameters
+            .insert("fx/reverb/mod_freq".to_string(), 2.0);
+        let preset = morph_presets(&from, &to, 0.25, 0.5);
+        assert_eq!(Some(&2.5), preset.parameters.get("fx/reverb/mod_freq"));
+
+        from.parameters.clear();
+        let preset = morph_presets(&from, &to, 0.25, 0.5);
+        assert_eq!(None, preset.parameters.get("fx/reverb/mod_freq"));
+        let preset = morph_presets(&from, &to, 0.75, 0.5);
+        assert_eq!(Some(&4.0), preset.parameters.get("fx/reverb/mod_freq"));
+
+        // Reset once switched
+        from.parameters.insert("fx/echo/mod".to_string(), 1.0);
+        let preset = morph_presets(&from, &to, 0.25, 0.5);
+        assert_eq!(Some(&1.0), preset.parameters.get("fx/echo/mod"));
+        let preset = morph_presets(&from, &to, 0.75, 0.5);
+        assert_eq!(None, preset.parameters.get("fx/echo/mod"));
+    }
+
+    #[rstest]
+    fn timed_test() {
+        let (from, to) = presets();
+        let start = Instant::now();
+        let settings = MorphSettings {
+            mode: MorphMode::Timed,
+            duration: 1.0,
+            switch_at: 0.5,
+        };
+        let mut morph = Morph::new(from, to, settings, start);
+        let preset = morph.update(start + Duration::from_millis(100)).unwrap();
+        assert!((preset.drone.detune - 0.02).abs() < 0.001);
+        // Rate limited
+        assert!(morph.update(start + Duration::from_millis(110)).is_none());
+        assert_eq!(
+            Some(start + Duration::from_millis(100) + MORPH_INTERVAL),
+            morph.next_update()
+        );
+        let preset = morph.update(start + Duration::from_secs(2)).unwrap();
+        assert!(morph.is_done());
+        assert_eq!(Pitch::D, preset.pitch);
+    }
+
+    #[rstest]
+    fn gesture_test() {
+        let (from, to) = presets();
+        let now = Instant::now();
+        let settings = MorphSettings {
+            mode: MorphMode::Gesture,
+            ..Default::default()
+        };
+        let mut morph = Morph::new(from, to, settings, now);
+        morph.gesture(GESTURE_END / 2.0);
+        morph.gesture(0.2);
+        let preset = morph.update(now).unwrap();
+        assert!((preset.mix.master.0 - 0.5).abs() < 0.001);
+        assert!(!morph.is_done());
+        // Not applied again until the hand moves
+        let later = now + MORPH_INTERVAL;
+        assert_eq!(None, morph.next_update());
+        assert!(morph.update(later).is_none());
+        morph.gesture(GESTURE_END * 0.75);
+        assert_eq!(Some(later), morph.next_update());
+        assert!(morph.update(later).is_some());
+        morph.gesture(GESTURE_END);
+        assert!(morph.is_done());
+    }
+}
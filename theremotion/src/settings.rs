@@ -1,30 +1,44 @@
+mod location;
+mod sharing;
+mod storage;
 mod v1;
 mod v2;
+mod v3;
+mod validation;
 use std::{
     collections::hash_map::DefaultHasher,
     hash::{Hash, Hasher},
     ops::RangeInclusive,
-    path::PathBuf,
+    time::Duration,
 };
 
-use anyhow::{Context, Ok, Result};
+use anyhow::{Ok, Result};
 use serde::{Deserialize, Serialize};
 use staff::{
     midi::{MidiNote, Octave},
-    scale::ScaleIntervals,
     Interval,
 };
 use std::sync::mpsc::Sender;
 
 use crate::{
-    controls::Controls,
-    solfege::{MoreScales, ScaleWindows},
+    controls::{Controls, EnvelopeControl},
+    solfege::{scale_catalog, ScaleWindows, Tuning},
     thread_dsp, HandType, IntervalF, MidiNoteF, OctaveInterval,
 };
 
-pub use self::v1::{EchoSettings, FxSettings, Handedness, MixSettings, NamedScale, ReverbSettings};
+pub use self::v1::Handedness;
 
-pub use self::v2::{Preset, Settings};
+pub use self::location::{SettingsLocation, CONFIG_ENV, USAGE};
+pub use self::sharing::{shared_files, SharedFile};
+pub use self::v3::{
+    ArpeggiatorSettings, ChorusSettings, DistortionSettings, EchoSettings, EnvelopeSettings,
+    EqSettings, FxSettings, GestureAction, GlideSettings, MixSettings, NamedScale, PhaserSettings,
+    Preset, ReverbSettings, Scene, Setlist, Settings, StrumSettings, VibratoSettings, WaveSettings,
+};
+pub use self::validation::Limits;
+
+/// Category of the scales saved by the user
+pub const USER_SCALE_CATEGORY: &str = "User";
 
 /// Default presets
 const PRESETS_BYTES: &[u8] = include_bytes!("settings/presets.yaml");
@@ -34,19 +48,34 @@ lazy_static::lazy_static! {
     static ref PRESETS: Vec<Preset> = serde_yaml::from_slice(PRESETS_BYTES).unwrap();
 }
 
+/// Current version of the settings format
+const CURRENT_VERSION: u32 = 3;
+
 /// Versionned application settings.
 ///
 /// This is the top-level serialized object
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
-#[serde(deny_unknown_fields)]
 enum Version {
     V1(v1::Settings),
     V2(v2::Settings),
+    V3(v3::Settings),
 }
 
 impl Default for Version {
     fn default() -> Self {
-        Version::V2(v2::Settings::default())
+        Version::V3(v3::Settings::default())
+    }
+}
+
+/// Content of a settings file written in a newer format than the current one
+fn newer_version(value: &serde_yaml::Value) -> Option<serde_yaml::Value> {
+    match value {
+        serde_yaml::Value::Tagged(tagged) => {
+            let tag = tagged.tag.to_string();
+            let version: u32 = tag.strip_prefix("!V")?.parse().ok()?;
+            (version > CURRENT_VERSION).then(|| tagged.value.clone())
+        }
+        _ => None,
     }
 }
 
@@ -56,76 +85,51 @@ impl Settings {
     where
         R: std::io::Read,
     {
-        let settings: Version = serde_yaml::from_reader(f)?;
+        let value: serde_yaml::Value = serde_yaml::from_reader(f)?;
+        let settings: Version =
+            serde_yaml::from_value(value.clone()).or_else(|e| match newer_version(&value) {
+                // Best effort, the newer fields are kept as they are
+                Some(value) => {
+                    log::warn!("The settings were written by a newer version of Theremotion");
+                    serde_yaml::from_value(value).map(Version::V3)
+                }
+                None => Err(e),
+            })?;
         match settings {
-            Version::V1(settings) => Ok(settings.into()),
-            Version::V2(settings) => Ok(settings),
-        }
-    }
-
-    fn path() -> Result<PathBuf> {
-        let directories = directories::ProjectDirs::from("", "", "Theremotion")
-            .context("No settings directory")?;
-        let directory = directories.config_dir();
-        Ok(directory.with_file_name("settings.yaml"))
-    }
-
-    /// Try reading the settings
-    fn try_read() -> Result<Self> {
-        let path = Settings::path()?;
-        log::debug!(
-            "Loading settings from {}",
-            path.to_str().unwrap_or_default()
-        );
-        let f = std::fs::File::open(path)?;
-        Self::from_reader(f)
-    }
-
-    /// Read the settings or get the default
-    pub fn read() -> Self {
-        Self::try_read().unwrap_or_default()
-    }
-
-    /// Save the settings
-    pub fn save(&self) -> Result<()> {
-        let path = Settings::path()?;
-        if let Some(parent) = path.as_path().parent() {
-            std::fs::create_dir_all(parent)?;
+            Version::V1(settings) => Ok(v2::Settings::from(settings).into()),
+            Version::V2(settings) => Ok(settings.into()),
+            Version::V3(settings) => Ok(settings),
         }
-
-        log::debug!("Saving settings to {}", path.to_str().unwrap_or_default());
-
-        let f = std::fs::OpenOptions::new()
-            .write(true)
-            .truncate(true)
-            .create(true)
-            .open(path)?;
-        let settings = Version::V2(self.clone());
-        serde_yaml::to_writer(f, &settings)?;
-        Ok(())
     }
 
-    pub fn system_and_user_scales(&self) -> impl Iterator<Item = (NamedScale, bool)> {
-        let user_scales = self.scales.clone().into_iter().map(|s| (s, true));
-        let system_scales = [
-            ("Chromatic", ScaleIntervals::all()),
-            ("Major", ScaleIntervals::major()),
-            ("Melodic Minor", ScaleIntervals::melodic_minor()),
-            ("Harmonic Minor", ScaleIntervals::harmonic_minor()),
-            ("Natural Minor", ScaleIntervals::natural_minor()),
-            ("Dorian", ScaleIntervals::dorian()),
-            ("Blues", ScaleIntervals::blues()),
-            ("Freygish", ScaleIntervals::freygish()),
-            ("Altered Dorian", ScaleIntervals::altered_dorian()),
-        ]
-        .map(|(name, scale)| (NamedScale::new(name.to_string(), scale), false));
+    pub fn system_and_user_scales(&self) -> impl Iterator<Item = (NamedScale, bool)> + '_ {
+        self.categorized_scales()
+            .map(|(_, scale, user)| (scale, user))
+    }
+
+    /// User and built-in scales with their category
+    pub fn categorized_scales(&self) -> impl Iterator<Item = (&str, NamedScale, bool)> + '_ {
+        let user_scales = self
+            .scales
+            .iter()
+            .map(|s| (USER_SCALE_CATEGORY, s.clone(), true));
+        let system_scales = scale_catalog().iter().flat_map(|category| {
+            category.scales.iter().map(|s| {
+                (
+                    category.name.as_str(),
+                    NamedScale::new(s.name.clone(), s.scale()),
+                    false,
+                )
+            })
+        });
         user_scales.chain(system_scales)
     }
 
     pub fn system_and_user_presets(&self) -> impl Iterator<Item = (&Preset, bool)> {
         let user_presets = self.presets.iter().map(|p| (p, true));
+        let directory_presets = self.directory_presets.iter().map(|p| (p, false));
         let system_presets = Preset::system_presets().iter().map(|p| (p, false));
-        user_presets.chain(system_presets)
+        user_presets.chain(directory_presets).chain(system_presets)
     }
 
     pub fn pitch_hand_type(&self) -> HandType {
@@ -186,12 +190,12 @@ impl Preset {
     }
 
     /// List all the notes of the current scale for the selected number of octaves
-    pub fn restricted_scale(&self) -> Vec<MidiNote> {
+    pub fn restricted_scale(&self) -> Vec<MidiNoteF> {
         self.scale_notes(self.note_range())
     }
 
     /// List all the notes of the current scale for the whole keyboard
-    pub fn full_scale(&self) -> Vec<MidiNote> {
+    pub fn full_scale(&self) -> Vec<MidiNoteF> {
         self.scale_notes(MidiNote::from_byte(0)..=MidiNote::from_byte(127))
     }
 
@@ -205,13 +209,31 @@ impl Preset {
         ScaleWindows::from_notes(self.restricted_scale())
     }
 
-    /// List all the notes in the current scale for the given range
-    fn scale_notes(&self, range: RangeInclusive<MidiNote>) -> Vec<MidiNote> {
-        crate::solfege::build_scale_notes(self.pitch, self.scale, range)
+    /// Tuning replacing the 12-TET scale, from the EDO scale or the loaded tuning
+    pub fn active_tuning(&self) -> Option<Tuning> {
+        self.edo
+            .as_ref()
+            .map(Tuning::from)
+            .or_else(|| self.tuning.clone())
+    }
+
+    /// List all the notes in the current scale, or tuning, for the given range
+    fn scale_notes(&self, range: RangeInclusive<MidiNote>) -> Vec<MidiNoteF> {
+        match self.active_tuning() {
+            Some(tuning) => tuning.notes(
+                self.root_note_f(),
+                MidiNoteF::from(*range.start())..=MidiNoteF::from(*range.end()),
+            ),
+            None => crate::solfege::build_scale_notes(self.pitch, self.scale, range)
+                .into_iter()
+                .map(MidiNoteF::from)
+                .collect(),
+        }
     }
 
     /// Send the relevant preset data to the DSP
     pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
+        controls.reference_pitch.send(tx, self.reference_pitch)?;
         controls.drone_detune.send(tx, self.drone.detune)?;
         let drone_interval = self.drone_interval();
         for (control, drone) in controls.drone_notes.iter().zip(self.drone_notes()) {
@@ -222,16 +244,35 @@ impl Preset {
             }
         }
 
+        self.wave.send_to_dsp(controls, tx)?;
+        controls.vibrato_rate.send(tx, self.vibrato.rate)?;
+        controls.vibrato_onset.send(tx, self.vibrato.onset)?;
+        controls.glide_time.send(tx, self.glide.time)?;
+        controls.glide_legato.send(tx, self.glide.legato);
+        self.lead_envelope
+            .send_to_dsp(&controls.lead_envelope, tx)?;
+        self.drone_envelope
+            .send_to_dsp(&controls.drone_envelope, tx)?;
         self.mix.send_to_dsp(controls, tx)?;
-        self.fx.send_to_dsp(controls, tx)?;
+        self.fx.send_to_dsp(controls, tx, self.tempo)?;
+        for control in &controls.parameters {
+            let value = self
+                .parameters
+                .get(&control.path)
+                .copied()
+                .unwrap_or(control.input.init);
+            control.send(tx, value)?;
+        }
         Ok(())
     }
 
-    pub fn drone_notes(&self) -> [Option<MidiNote>; 4] {
+    pub fn drone_notes(&self) -> Vec<Option<MidiNote>> {
         let root_note = self.root_note();
         self.drone
             .intervals
+            .iter()
             .map(|drone| drone.map(|drone| root_note + drone))
+            .collect()
     }
 
     pub fn system_presets() -> &'static Vec<Self> {
@@ -245,6 +286,80 @@ impl Preset {
     }
 }
 
+impl Setlist {
+    /// Scene being played
+    pub fn current_scene(&self) -> Option<&Scene> {
+        self.scenes.get(self.current)
+    }
+
+    /// Scene played after the current one
+    pub fn next_scene(&self) -> Option<&Scene> {
+        self.scenes.get(self.current + 1)
+    }
+
+    /// Go to a scene, staying in the setlist
+    pub fn go_to(&mut self, index: usize) -> Option<&Scene> {
+        self.current = index.min(self.scenes.len().saturating_sub(1));
+        self.current_scene()
+    }
+
+    /// Move from the current scene
+    pub fn step(&mut self, offset: isize) -> Option<&Scene> {
+        self.go_to(self.current.saturating_add_signed(offset))
+    }
+
+    /// Remove a scene, keeping the current one if possible
+    pub fn remove(&mut self, index: usize) {
+        if index < self.scenes.len() {
+            self.scenes.remove(index);
+            if index < self.current {
+                self.current -= 1;
+            }
+            self.current = self.current.min(self.scenes.len().saturating_sub(1));
+        }
+    }
+}
+
+impl ArpeggiatorSettings {
+    /// Position in arpeggiator steps after the given number of beats
+    pub fn steps(&self, beats: f32) -> f32 {
+        beats * self.division.max(1) as f32
+    }
+}
+
+impl StrumSettings {
+    /// Delay between two strings of a strum
+    pub fn spacing_duration(&self) -> Duration {
+        Duration::from_secs_f32(self.spacing.max(0.0))
+    }
+}
+
+impl WaveSettings {
+    pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
+        controls.wave_sine.send(tx, self.sine)?;
+        controls.wave_triangle.send(tx, self.triangle)?;
+        controls.wave_square.send(tx, self.square)?;
+        controls.wave_pulse_width.send(tx, self.pulse_width)?;
+        controls.wave_saw.send(tx, self.saw)?;
+        controls.wave_theremin.send(tx, self.theremin)?;
+        Ok(())
+    }
+}
+
+impl EnvelopeSettings {
+    pub fn send_to_dsp(
+        &self,
+        control: &EnvelopeControl,
+        tx: &Sender<thread_dsp::Msg>,
+    ) -> Result<()> {
+        control.attack.send(tx, self.attack)?;
+        control.decay.send(tx, self.decay)?;
+        control.sustain.send(tx, self.sustain)?;
+        control.release.send(tx, self.release)?;
+        Ok(())
+    }
+}
+
 impl MixSettings {
     pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
         controls.mix_drone_volume.send(tx, self.drone)?;
@@ -256,8 +371,23 @@ impl MixSettings {
 }
 
 impl EchoSettings {
-    pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
-        controls.echo_duration.send(tx, self.duration)?;
+    /// Echo duration in seconds, following the tempo when synchronised
+    pub fn effective_duration(&self, tempo: f32) -> f32 {
+        self.sync
+            .duration(tempo)
+            .map(|d| d.as_secs_f32())
+            .unwrap_or(self.duration)
+    }
+
+    pub fn send_to_dsp(
+        &self,
+        controls: &Controls,
+        tx: &Sender<thread_dsp::Msg>,
+        tempo: f32,
+    ) -> Result<()> {
+        controls
+            .echo_duration
+            .send(tx, self.effective_duration(tempo))?;
         controls.echo_feedback.send(tx, self.feedback)?;
         controls.echo_mix.send(tx, self.mix)?;
         Ok(())
@@ -274,10 +404,54 @@ impl ReverbSettings {
     }
 }
 
-impl FxSettings {
+impl DistortionSettings {
     pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
-        self.echo.send_to_dsp(controls, tx)?;
+        controls.distortion_mix.send(tx, self.mix)?;
+        controls.distortion_drive.send(tx, self.drive)?;
+        Ok(())
+    }
+}
+
+impl EqSettings {
+    pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
+        controls.eq_low.send(tx, self.low)?;
+        controls.eq_mid.send(tx, self.mid)?;
+        controls.eq_high.send(tx, self.high)?;
+        Ok(())
+    }
+}
+
+impl ChorusSettings {
+    pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
+        controls.chorus_mix.send(tx, self.mix)?;
+        controls.chorus_rate.send(tx, self.rate)?;
+        controls.chorus_depth.send(tx, self.depth)?;
+        Ok(())
+    }
+}
+
+impl PhaserSettings {
+    pub fn send_to_dsp(&self, controls: &Controls, tx: &Sender<thread_dsp::Msg>) -> Result<()> {
+        controls.phaser_mix.send(tx, self.mix)?;
+        controls.phaser_rate.send(tx, self.rate)?;
+        controls.phaser_feedback.send(tx, self.feedback)?;
+        Ok(())
+    }
+}
+
+impl FxSettings {
+    pub fn send_to_dsp(
+        &self,
+        controls: &Controls,
+        tx: &Sender<thread_dsp::Msg>,
+        tempo: f32,
+    ) -> Result<()> {
+        self.echo.send_to_dsp(controls, tx, tempo)?;
         self.reverb.send_to_dsp(controls, tx)?;
+        self.distortion.send_to_dsp(controls, tx)?;
+        self.eq.send_to_dsp(controls, tx)?;
+        self.chorus.send_to_dsp(controls, tx)?;
+        self.phaser.send_to_dsp(controls, tx)?;
         Ok(())
     }
 }
@@ -287,7 +461,7 @@ mod tests {
     use rstest::rstest;
     use staff::midi::{MidiNote, Octave};
 
-    use crate::Volume;
+    use crate::{morph::MorphMode, solfege::ArpPattern, tempo::TempoSync, Volume};
 
     use super::*;
 
@@ -310,9 +484,159 @@ mod tests {
         assert_eq!(Volume(0.8), settings.current_preset.fx.reverb.mix);
     }
 
+    #[rstest]
+    fn from_v2() {
+        let f = std::fs::File::open("src/settings/v2.yaml").unwrap();
+        let settings = Settings::from_reader(f).unwrap();
+        assert_eq!("", settings.app_version);
+        assert_eq!(Octave::ONE, settings.current_preset.drone_octave);
+        assert_eq!(
+            vec![Some(Interval::new(0)), Some(Interval::new(7)), None, None],
+            settings.current_preset.drone.intervals
+        );
+        assert!(settings.current_preset.drone.pluck_drone);
+        assert_eq!(Volume(0.8), settings.current_preset.mix.guitar);
+        assert_eq!(0.46, settings.current_preset.fx.echo.feedback);
+        assert_eq!(Volume(0.8), settings.current_preset.fx.reverb.mix);
+        assert_eq!(1, settings.presets.len());
+        assert_eq!("Mine", settings.scales[0].name);
+        assert_eq!(Handedness::LeftHanded, settings.system.handedness);
+
+        // The later additions get their default value
+        assert_eq!(440.0, settings.current_preset.reference_pitch);
+        assert_eq!(120.0, settings.current_preset.tempo);
+        assert_eq!(TempoSync::Free, settings.current_preset.fx.echo.sync);
+        assert_eq!(GestureAction::None, settings.system.fist_action);
+        assert_eq!(MorphMode::Off, settings.morph.mode);
+        assert!(settings.setlist.scenes.is_empty());
+    }
+
+    #[rstest]
+    fn from_v3() {
+        let f = std::fs::File::open("src/settings/v3.yaml").unwrap();
+        let settings = Settings::from_reader(f).unwrap();
+        assert_eq!("9.9.0", settings.app_version);
+        assert!(settings.extra.contains_key("future_option"));
+        assert!(settings
+            .current_preset
+            .extra
+            .contains_key("future_preset_option"));
+        assert!(settings.system.extra.contains_key("future_system_option"));
+
+        // Unknown nested fields are ignored
+        assert_eq!(
+            TempoSync::DottedEighth,
+            settings.current_preset.fx.echo.sync
+        );
+        assert_eq!(
+            ArpPattern::UpDown,
+            settings.current_preset.arpeggiator.pattern
+        );
+        assert_eq!(442.0, settings.current_preset.reference_pitch);
+        assert_eq!(96.0, settings.current_preset.tempo);
+        assert_eq!(GestureAction::TapTempo, settings.system.fist_action);
+        assert_eq!(MorphMode::Timed, settings.morph.mode);
+        assert_eq!("Intro", settings.setlist.scenes[0].notes);
+    }
+
+    #[rstest]
+    #[case("src/settings/v1.yaml")]
+    #[case("src/settings/v2.yaml")]
+    #[case("src/settings/v3.yaml")]
+    fn migration(#[case] fixture: &str) {
+        let f = std::fs::File::open(fixture).unwrap();
+        let settings = Settings::from_reader(f).unwrap();
+        let yaml = serde_yaml::to_string(&Version::V3(settings.clone())).unwrap();
+        assert!(yaml.starts_with("!V3"));
+        assert_eq!(settings, Settings::from_reader(yaml.as_bytes()).unwrap());
+    }
+
+    #[rstest]
+    fn newer_version() {
+        let yaml = "!V9\ncurrent_preset:\n  name: Future\n  new_field: 1\nnew_section: {}\n";
+        let settings = Settings::from_reader(yaml.as_bytes()).unwrap();
+        assert_eq!("Future", settings.current_preset.name);
+        assert!(settings.extra.contains_key("new_section"));
+        let yaml = "!V9\ncurrent_preset:\n  fx:\n    reverb:\n      mix: 0.5\n      new_field: 1\n";
+        let settings = Settings::from_reader(yaml.as_bytes()).unwrap();
+        assert_eq!(Volume(0.5), settings.current_preset.fx.reverb.mix);
+        assert!(Settings::from_reader("!V1\nunknown: 1\n".as_bytes()).is_err());
+    }
+
+    #[rstest]
+    #[case(TempoSync::Free, 120.0, 0.3)]
+    #[case(TempoSync::Quarter, 120.0, 0.5)]
+    #[case(TempoSync::DottedEighth, 60.0, 0.75)]
+    fn echo_duration(#[case] sync: TempoSync, #[case] tempo: f32, #[case] expected: f32) {
+        let echo = EchoSettings {
+            duration: 0.3,
+            sync,
+            ..Default::default()
+        };
+        assert!((echo.effective_duration(tempo) - expected).abs() < 0.001);
+    }
+
+    #[rstest]
+    #[case(4, 1.5, 6.0)]
+    #[case(3, 2.0, 6.0)]
+    #[case(0, 2.5, 2.5)]
+    fn steps(#[case] division: u8, #[case] beats: f32, #[case] expected: f32) {
+        let arpeggiator = ArpeggiatorSettings {
+            division,
+            ..Default::default()
+        };
+        assert_eq!(expected, arpeggiator.steps(beats));
+    }
+
     #[rstest]
     fn default() {
         // Dynamically deserialized at runtime...
         assert!(!Preset::system_presets().is_empty());
     }
+
+    #[rstest]
+    fn categorized_scales() {
+        let mut settings = Settings::default();
+        settings.scales.push(NamedScale::new(
+            "Mine".to_string(),
+            staff::scale::ScaleIntervals::major(),
+        ));
+        let scales: Vec<_> = settings.categorized_scales().collect();
+        assert_eq!(USER_SCALE_CATEGORY, scales[0].0);
+        assert_eq!("Mine", scales[0].1.name);
+        assert!(scales
+            .iter()
+            .any(|(category, scale, user)| *category == "World"
+                && scale.name == "Freygish"
+                && !user));
+    }
+
+    #[rstest]
+    fn setlist() {
+        let scene = |name: &str| Scene {
+            preset: name.to_string(),
+            notes: String::new(),
+        };
+        let mut setlist = Setlist {
+            scenes: vec![scene("A"), scene("B"), scene("C")],
+            current: 0,
+        };
+        assert_eq!("B", setlist.next_scene().unwrap().preset);
+        assert_eq!("A", setlist.step(-1).unwrap().preset);
+        assert_eq!("C", setlist.step(5).unwrap().preset);
+        assert_eq!(None, setlist.next_scene());
+        setlist.remove(0);
+        assert_eq!("C", setlist.current_scene().unwrap().preset);
+        setlist.remove(1);
+        assert_eq!("B", setlist.current_scene().unwrap().preset);
+    }
+
+    #[rstest]
+    #[case("Harmonic Minor", "minor", true)]
+    #[case("Harmonic Minor", " HARMONIC ", true)]
+    #[case("Harmonic Minor", "major", false)]
+    fn scale_search(#[case] name: &str, #[case] search: &str, #[case] expected: bool) {
+        let scale = NamedScale::new(name.to_string(), staff::scale::ScaleIntervals::all());
+        assert_eq!(expected, scale.matches(search));
+    }
 }
@@ -0,0 +1,193 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use super::Settings;
+
+/// Environment variable overriding the settings location
+pub const CONFIG_ENV: &str = "THEREMOTION_CONFIG";
+
+/// Command line help
+pub const USAGE: &str = "Usage: theremotion [--config <file.yaml | directory>] [--profile <name>]
+
+    --config   Settings file, or directory holding the settings and profiles.
+               Can also be set with the THEREMOTION_CONFIG environment variable.
+    --profile  Named set of settings, like \"studio\" or \"stage\"";
+
+/// Name of the settings file in the settings directory
+const SETTINGS_FILE: &str = "settings.yaml";
+
+/// Directory of the profiles in the settings directory
+const PROFILES_DIR: &str = "profiles";
+
+/// Where the settings are read from and saved to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SettingsLocation {
+    /// Settings file or directory replacing the default one
+    pub config: Option<PathBuf>,
+    /// Named set of settings
+    pub profile: Option<String>,
+}
+
+impl SettingsLocation {
+    /// Parse the command line arguments, the environment variable being
+    /// overridden by the arguments
+    pub fn from_args(args: impl IntoIterator<Item = String>, env: Option<String>) -> Result<Self> {
+        let mut location = Self {
+            config: env.filter(|e| !e.is_empty()).map(PathBuf::from),
+            profile: None,
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if !matches!(name.as_str(), "--config" | "--profile") {
+                bail!("Unknown argument {}", name);
+            }
+            let value = match value.or_else(|| args.next()) {
+                Some(value) => value,
+                None => bail!("Missing value for {}", name),
+            };
+            if name == "--config" {
+                location.config = Some(PathBuf::from(value));
+            } else {
+                location.profile = Some(value);
+            }
+        }
+
+        if let Some(profile) = &location.profile {
+            let valid = !profile.is_empty()
+                && profile
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+            if !valid {
+                bail!("Invalid profile name \"{}\"", profile);
+            }
+        }
+        Ok(location)
+    }
+
+    /// Path of the settings file.
+    ///
+    /// When the default directory is used, the settings file of the previous
+    /// versions is moved into it.
+    pub fn path(&self) -> Result<PathBuf> {
+        self.path_in(|| {
+            let directories = directories::ProjectDirs::from("", "", "Theremotion")
+                .context("No settings directory")?;
+            let directory = directories.config_dir();
+            if let Err(e) = migrate_legacy_file(directory) {
+                log::warn!("Failed to move the previous settings file: {}", e);
+            }
+            Ok(directory.to_path_buf())
+        })
+    }
+
+    /// Path of the settings file, given the default settings directory
+    fn path_in(&self, default_dir: impl FnOnce() -> Result<PathBuf>) -> Result<PathBuf> {
+        let directory = match &self.config {
+            Some(config) if is_settings_file(config) => {
+                if self.profile.is_some() {
+                    bail!("A profile can't be used with a settings file");
+                }
+                return Ok(config.clone());
+            }
+            Some(config) => config.clone(),
+            None => default_dir()?,
+        };
+        Ok(match &self.profile {
+            Some(profile) => directory.join(PROFILES_DIR).join(format!("{profile}.yaml")),
+            None => directory.join(SETTINGS_FILE),
+        })
+    }
+}
+
+/// The path designates a file rather than a directory
+fn is_settings_file(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|e| e.to_str()),
+        Some("yaml" | "yml")
+    )
+}
+
+/// Move the settings file saved beside the settings directory by the previous
+/// versions into it
+fn migrate_legacy_file(directory: &Path) -> Result<()> {
+    let legacy = directory.with_file_name(SETTINGS_FILE);
+    let path = directory.join(SETTINGS_FILE);
+    if path.exists() || !legacy.is_file() {
+        return Ok(());
+    }
+    // The legacy name is generic, only move it if it is ours
+    let f = std::fs::File::open(&legacy)?;
+    if Settings::from_reader(f).is_err() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(directory)?;
+    if std::fs::rename(&legacy, &path).is_err() {
+        std::fs::copy(&legacy, &path)?;
+        std::fs::remove_file(&legacy)?;
+    }
+    log::info!(
+        "Moved the settings from {} to {}",
+        legacy.display(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::settings::storage::tests::TestDir;
+
+    fn args(args: &str) -> Vec<String> {
+        args.split_whitespace().map(|a| a.to_string()).collect()
+    }
+
+    #[rstest]
+    #[case("", None, None, "/conf/settings.yaml")]
+    #[case("--profile stage", None, None, "/conf/profiles/stage.yaml")]
+    #[case("--profile=stage", Some("/env"), None, "/env/profiles/stage.yaml")]
+    #[case("", Some("/env/mine.yaml"), None, "/env/mine.yaml")]
+    #[case("--config /arg.yml", Some("/env"), None, "/arg.yml")]
+    #[case("--config /env/mine.yaml --profile stage", None, Some(()), "")]
+    #[case("--profile ../stage", None, Some(()), "")]
+    #[case("--profile", None, Some(()), "")]
+    #[case("--unknown", None, Some(()), "")]
+    fn path_test(
+        #[case] arguments: &str,
+        #[case] env: Option<&str>,
+        #[case] error: Option<()>,
+        #[case] expected: &str,
+    ) {
+        let path = SettingsLocation::from_args(args(arguments), env.map(|e| e.to_string()))
+            .and_then(|location| location.path_in(|| Ok(PathBuf::from("/conf"))));
+        match error {
+            Some(()) => assert!(path.is_err()),
+            None => assert_eq!(PathBuf::from(expected), path.unwrap()),
+        }
+    }
+
+    #[rstest]
+    fn migrate_test() {
+        let root = TestDir::new("migrate");
+        let directory = root.join("Theremotion");
+        let legacy = root.join(SETTINGS_FILE);
+
+        // Not a Theremotion file
+        std::fs::write(&legacy, "other: app").unwrap();
+        migrate_legacy_file(&directory).unwrap();
+        assert!(legacy.exists());
+        assert!(!directory.join(SETTINGS_FILE).exists());
+
+        Settings::default().save_to(&legacy).unwrap();
+        migrate_legacy_file(&directory).unwrap();
+        assert!(!legacy.exists());
+        assert!(directory.join(SETTINGS_FILE).exists());
+    }
+}
@@ -0,0 +1,245 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{NamedScale, Preset, Settings};
+
+/// Standalone file holding presets or scales, to share them between players
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub enum SharedFile {
+    /// A single preset
+    Preset(Preset),
+    /// A single scale
+    Scale(NamedScale),
+    /// A set of presets and scales
+    Bundle {
+        #[serde(default)]
+        presets: Vec<Preset>,
+        #[serde(default)]
+        scales: Vec<NamedScale>,
+    },
+}
+
+impl SharedFile {
+    /// Read a shared file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let f = std::fs::File::open(path)?;
+        Ok(serde_yaml::from_reader(f)?)
+    }
+
+    /// Write a shared file, refusing to replace an existing one
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let f = match std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(path)
+        {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                anyhow::bail!("{} already exists", path.display())
+            }
+            f => f?,
+        };
+        serde_yaml::to_writer(f, self)?;
+        Ok(())
+    }
+}
+
+/// Shared files of a directory, in name order
+pub fn shared_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().map(|e| e == "yaml").unwrap_or(false))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// Name not taken yet, adding a number to the original one if needed
+fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+    (2..)
+        .map(|i| format!("{name} ({i})"))
+        .find(|candidate| !taken(candidate))
+        .expect("No free name")
+}
+
+impl Settings {
+    /// Bundle of all the user presets and scales
+    pub fn export_bundle(&self) -> SharedFile {
+        SharedFile::Bundle {
+            presets: self.presets.clone(),
+            scales: self.scales.clone(),
+        }
+    }
+
+    /// Load the presets of the presets directory, if any.
+    ///
+    /// Returns the errors of the files that could not be read.
+    pub fn load_directory_presets(&mut self) -> Vec<String> {
+        self.directory_presets.clear();
+        let directory = match &self.system.presets_directory {
+            Some(directory) => directory.clone(),
+            None => return vec![],
+        };
+        let mut errors = vec![];
+        for path in shared_files(&directory) {
+            match SharedFile::load(&path) {
+                Ok(SharedFile::Preset(preset)) => self.directory_presets.push(preset),
+                Ok(SharedFile::Bundle { presets, .. }) => self.directory_presets.extend(presets),
+                Ok(SharedFile::Scale(_)) => {}
+                Err(e) => errors.push(format!("{}: {}", path.display(), e)),
+            }
+        }
+        errors
+    }
+
+    /// Add the content of a shared file to the user presets and scales.
+    ///
+    /// Already known items are skipped, and items colliding with another one
+    /// of the same name are renamed. Returns the number of added items.
+    pub fn import(&mut self, file: SharedFile) -> usize {
+        let (presets, scales) = match file {
+            SharedFile::Preset(preset) => (vec![preset], vec![]),
+            SharedFile::Scale(scale) => (vec![], vec![scale]),
+            SharedFile::Bundle { presets, scales } => (presets, scales),
+        };
+        let mut count = 0;
+
+        for mut preset in presets {
+            if self.system_and_user_presets().any(|(p, _)| p == &preset) {
+                continue;
+            }
+            preset.name = unique_name(&preset.name, |name| {
+                self.system_and_user_presets().any(|(p, _)| p.name == name)
+            });
+            self.presets.push(preset);
+            count += 1;
+        }
+
+        for mut scale in scales {
+            if self.system_and_user_scales().any(|(s, _)| s == scale) {
+                continue;
+            }
+            scale.name = unique_name(&scale.name, |name| {
+                self.system_and_user_scales().any(|(s, _)| s.name == name)
+            });
+            self.scales.push(scale);
+            count += 1;
+        }
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use staff::scale::ScaleIntervals;
+
+    use super::*;
+
+    fn preset(name: &str, tempo: f32) -> Preset {
+        Preset {
+            name: name.to_string(),
+            tempo,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    fn unique_name_test() {
+        let taken = ["A", "A (2)"];
+        assert_eq!("B", unique_name("B", |n| taken.contains(&n)));
+        assert_eq!("A (3)", unique_name("A", |n| taken.contains(&n)));
+    }
+
+    #[rstest]
+    fn import_test() {
+        let mut settings = Settings {
+            presets: vec![preset("Mine", 100.0)],
+            ..Default::default()
+        };
+        let count = settings.import(SharedFile::Bundle {
+            presets: vec![
+                // Already there
+                preset("Mine", 100.0),
+                // Same name, different content
+                preset("Mine", 90.0),
+                preset("Other", 90.0),
+            ],
+            scales: vec![
+                // Same name as a built-in scale
+                NamedScale::new("Major".to_string(), ScaleIntervals::blues()),
+                NamedScale::new("Major".to_string(), ScaleIntervals::major()),
+            ],
+        });
+        assert_eq!(3, count);
+        let names: Vec<&str> = settings.presets.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(vec!["Mine", "Mine (2)", "Other"], names);
+        assert_eq!(1, settings.scales.len());
+        assert_eq!("Major (2)", settings.scales[0].name);
+    }
+
+    #[rstest]
+    fn directory_presets_test() {
+        let directory =
+            std::env::temp_dir().join(format!("theremotion-presets-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        SharedFile::Preset(preset("B", 100.0))
+            .save(directory.join("b.yaml"))
+            .unwrap();
+        SharedFile::Bundle {
+            presets: vec![preset("A", 100.0)],
+            scales: vec![],
+        }
+        .save(directory.join("a.yaml"))
+        .unwrap();
+        std::fs::write(directory.join("broken.yaml"), "!Preset [").unwrap();
+        std::fs::write(directory.join("notes.txt"), "Not a preset").unwrap();
+
+        let mut settings = Settings::default();
+        settings.system.presets_directory = Some(directory);
+        let errors = settings.load_directory_presets();
+        assert_eq!(1, errors.len());
+        let names: Vec<&str> = settings
+            .directory_presets
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(vec!["A", "B"], names);
+    }
+
+    #[rstest]
+    fn no_overwrite_test() {
+        let directory =
+            std::env::temp_dir().join(format!("theremotion-export-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("a.yaml");
+        SharedFile::Preset(preset("A", 100.0)).save(&path).unwrap();
+        assert!(SharedFile::Preset(preset("B", 100.0)).save(&path).is_err());
+        assert_eq!(
+            SharedFile::Preset(preset("A", 100.0)),
+            SharedFile::load(&path).unwrap()
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[rstest]
+    fn round_trip_test() {
+        let file = SharedFile::Scale(NamedScale::new("Mine".to_string(), ScaleIntervals::major()));
+        let yaml = serde_yaml::to_string(&file).unwrap();
+        assert_eq!(file, serde_yaml::from_str(&yaml).unwrap());
+    }
+}
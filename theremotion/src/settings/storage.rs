@@ -0,0 +1,217 @@
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+
+use super::{Settings, Version};
+
+/// Number of backups kept, one per session
+const BACKUP_COUNT: usize = 3;
+
+/// Path of a backup, 1 being the most recent
+fn backup_path(path: &Path, index: usize) -> PathBuf {
+    path.with_extension(format!("{index}.bak"))
+}
+
+/// Shift the backups and keep a copy of the current file
+fn rotate_backups(path: &Path) -> Result<()> {
+    for index in (1..BACKUP_COUNT).rev() {
+        let backup = backup_path(path, index);
+        if backup.exists() {
+            std::fs::rename(&backup, backup_path(path, index + 1))?;
+        }
+    }
+    std::fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// Move an unreadable file aside so that it is not overwritten
+fn quarantine(path: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let aside = path.with_extension(format!("corrupted-{timestamp}.yaml"));
+    std::fs::rename(path, &aside)?;
+    Ok(aside)
+}
+
+impl Settings {
+    /// Read a settings file
+    pub fn read_file(path: &Path) -> Result<Self> {
+        let f = std::fs::File::open(path)?;
+        Self::from_reader(f)
+    }
+
+    /// Read the settings, or get the default ones.
+    ///
+    /// A corrupted file is kept aside and the most recent readable backup is
+    /// used instead. Returns a message to show to the user when the settings
+    /// file could not be used.
+    pub fn read(path: &Path) -> (Self, Option<String>) {
+        log::debug!("Loading settings from {}", path.display());
+        if !path.exists() {
+            return (Self::default(), None);
+        }
+        let error = match Self::read_file(path) {
+            Ok(settings) => {
+                if let Err(e) = rotate_backups(path) {
+                    log::warn!("Failed to back up the settings: {}", e);
+                }
+                return (settings, None);
+            }
+            Err(e) => e,
+        };
+        log::error!("Failed to read the settings: {:#}", error);
+
+        let kept = match quarantine(path) {
+            Ok(aside) => format!("it was kept as {}", aside.display()),
+            Err(e) => format!("it could not be moved aside: {e}"),
+        };
+        let backup = (1..=BACKUP_COUNT)
+            .map(|index| backup_path(path, index))
+            .find_map(|backup| Self::read_file(&backup).ok().map(|s| (s, backup)));
+        let message = match &backup {
+            Some((_, backup)) => format!(
+                "Unreadable settings ({error}), {kept}. Restored {}.",
+                backup.display()
+            ),
+            None => format!("Unreadable settings ({error}), {kept}. Using the defaults."),
+        };
+        log::error!("{}", message);
+        let settings = backup.map(|(settings, _)| settings).unwrap_or_default();
+        (settings, Some(message))
+    }
+
+    /// Save the settings.
+    ///
+    /// The settings are written to a temporary file first, then moved in
+    /// place, so that an interrupted write can't corrupt the existing file.
+    pub fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        log::debug!("Saving settings to {}", path.display());
+
+        let temporary = path.with_extension("yaml.tmp");
+        let mut f = std::fs::File::create(&temporary)?;
+        let settings = Settings {
+            app_version: crate::VERSION.to_string(),
+            ..self.clone()
+        };
+        serde_yaml::to_writer(&mut f, &Version::V3(settings))?;
+        f.flush()?;
+        f.sync_all()?;
+        drop(f);
+        std::fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::{
+        ops::Deref,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Empty directory for a test, removed afterwards
+    pub(crate) struct TestDir(PathBuf);
+
+    impl TestDir {
+        pub(crate) fn new(name: &str) -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let dir = std::env::temp_dir().join(format!(
+                "theremotion-{}-{}-{}",
+                name,
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Deref for TestDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn settings(name: &str) -> Settings {
+        let mut settings = Settings::default();
+        settings.current_preset.name = name.to_string();
+        settings
+    }
+
+    #[rstest]
+    fn save_read_test() {
+        let dir = TestDir::new("save");
+        let path = dir.join("settings.yaml");
+        assert_eq!(None, Settings::read(&path).1);
+
+        settings("First").save_to(&path).unwrap();
+        settings("Second").save_to(&path).unwrap();
+        assert!(!path.with_extension("yaml.tmp").exists());
+        let (read, message) = Settings::read(&path);
+        assert_eq!(None, message);
+        assert_eq!("Second", read.current_preset.name);
+        assert!(backup_path(&path, 1).exists());
+    }
+
+    #[rstest]
+    fn rotate_test() {
+        let dir = TestDir::new("rotate");
+        let path = dir.join("settings.yaml");
+        for name in ["A", "B", "C", "D", "E"] {
+            settings(name).save_to(&path).unwrap();
+            Settings::read(&path);
+        }
+        let backups: Vec<String> = (1..=BACKUP_COUNT)
+            .map(|index| {
+                Settings::read_file(&backup_path(&path, index))
+                    .unwrap()
+                    .current_preset
+                    .name
+            })
+            .collect();
+        assert_eq!(vec!["E", "D", "C"], backups);
+        assert!(!backup_path(&path, BACKUP_COUNT + 1).exists());
+    }
+
+    #[rstest]
+    fn corrupted_test() {
+        let dir = TestDir::new("corrupted");
+        let path = dir.join("settings.yaml");
+        settings("Good").save_to(&path).unwrap();
+        Settings::read(&path);
+        std::fs::write(&path, "V2:\n  current_preset: [").unwrap();
+
+        let (read, message) = Settings::read(&path);
+        assert_eq!("Good", read.current_preset.name);
+        assert!(message.is_some());
+        assert!(!path.exists());
+        let kept = std::fs::read_dir(&*dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().contains("corrupted"));
+        assert!(kept);
+    }
+}
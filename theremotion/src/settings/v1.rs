@@ -1,8 +1,3 @@
-use std::{
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
-
 use serde::{Deserialize, Serialize};
 use staff::{
     midi::{MidiNote, Octave},
@@ -233,18 +228,3 @@ pub struct NamedScale {
     /// Corresponding scale
     pub scale: ScaleIntervals,
 }
-
-impl NamedScale {
-    /// Creates a new [`NamedScale`].
-    pub fn new(name: String, scale: ScaleIntervals) -> Self {
-        Self { name, scale }
-    }
-
-    /// Pseudo hash for identification in the ui
-    pub fn id(&self) -> i32 {
-        let mut hasher = DefaultHasher::default();
-        self.name.hash(&mut hasher);
-        self.scale.bits.hash(&mut hasher);
-        hasher.finish() as i32
-    }
-}
@@ -0,0 +1,703 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    path::PathBuf,
+};
+
+use super::{
+    v1::{self, Handedness},
+    v2,
+};
+use crate::{
+    morph::MorphSettings,
+    solfege::{ArpPattern, EdoScale, Tuning},
+    tempo::TempoSync,
+    vibrato::VibratoGesture,
+    Volume,
+};
+use serde::{Deserialize, Serialize};
+use staff::{midi::Octave, scale::ScaleIntervals, Interval, Pitch};
+
+/// Application settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct Settings {
+    /// Version of Theremotion that wrote the settings
+    #[serde(default)]
+    pub app_version: String,
+
+    /// Current sound settings
+    #[serde(default)]
+    pub current_preset: Preset,
+
+    /// Saved presets
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+
+    /// Saved scales
+    #[serde(default)]
+    pub scales: Vec<NamedScale>,
+
+    /// System settings
+    #[serde(default)]
+    pub system: System,
+
+    /// Transitions between presets
+    #[serde(default)]
+    pub morph: MorphSettings,
+
+    /// Presets of the show
+    #[serde(default)]
+    pub setlist: Setlist,
+
+    /// Fields written by a newer version, kept when saving
+    #[serde(flatten)]
+    pub extra: serde_yaml::Mapping,
+
+    /// Presets loaded from the presets directory
+    #[serde(skip)]
+    pub directory_presets: Vec<Preset>,
+}
+
+impl From<v2::Settings> for Settings {
+    fn from(value: v2::Settings) -> Self {
+        Self {
+            current_preset: value.current_preset.into(),
+            presets: value.presets.into_iter().map(|p| p.into()).collect(),
+            scales: value.scales.into_iter().map(|s| s.into()).collect(),
+            system: value.system.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Ordered list of presets played during a show
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct Setlist {
+    /// Scenes in the playing order
+    pub scenes: Vec<Scene>,
+    /// Index of the current scene
+    pub current: usize,
+}
+
+/// Song of the setlist
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct Scene {
+    /// Name of the played preset
+    pub preset: String,
+    /// Notes for the performer
+    pub notes: String,
+}
+
+/// Sound preset
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Preset {
+    /// Name of the preset
+    pub name: String,
+
+    /// Octave of the root note
+    pub lead_octave: Octave,
+
+    /// Octave of the guitar sound
+    pub guitar_octave: Octave,
+
+    /// Octave of the drone
+    pub drone_octave: Octave,
+
+    /// Pitch of the root note
+    pub pitch: Pitch,
+
+    /// Scale of the autotune
+    pub scale: ScaleIntervals,
+
+    /// Microtonal tuning replacing the scale, if any
+    pub tuning: Option<Tuning>,
+
+    /// Scale in an equal division of the octave replacing the scale, if any
+    pub edo: Option<EdoScale>,
+
+    /// Frequency of A4 (Hz)
+    pub reference_pitch: f32,
+
+    /// Current drone
+    pub drone: DroneSettings,
+
+    /// Lead waveforms
+    pub wave: WaveSettings,
+
+    /// Lead vibrato
+    pub vibrato: VibratoSettings,
+
+    /// Lead slides between notes
+    pub glide: GlideSettings,
+
+    /// Amplitude envelope of the lead voices
+    pub lead_envelope: EnvelopeSettings,
+
+    /// Amplitude envelope of the drone notes
+    pub drone_envelope: EnvelopeSettings,
+
+    /// Volume settings
+    pub mix: MixSettings,
+
+    /// Effects settings
+    pub fx: FxSettings,
+
+    /// Tempo in beats per minute
+    pub tempo: f32,
+
+    /// Arpeggiator settings
+    pub arpeggiator: ArpeggiatorSettings,
+
+    /// Guitar strum settings
+    pub strum: StrumSettings,
+
+    /// Values of the other DSP parameters, by path
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, f32>,
+
+    /// Fields written by a newer version, kept when saving
+    #[serde(flatten)]
+    pub extra: serde_yaml::Mapping,
+}
+
+impl Default for Preset {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            lead_octave: Octave::THREE,
+            guitar_octave: Octave::THREE,
+            drone_octave: Octave::THREE,
+            pitch: Pitch::C,
+            scale: ScaleIntervals::major(),
+            tuning: None,
+            edo: None,
+            reference_pitch: 440.0,
+            drone: Default::default(),
+            wave: Default::default(),
+            vibrato: Default::default(),
+            glide: Default::default(),
+            lead_envelope: Default::default(),
+            drone_envelope: EnvelopeSettings {
+                attack: 0.05,
+                release: 0.3,
+                ..Default::default()
+            },
+            mix: Default::default(),
+            fx: Default::default(),
+            tempo: 120.0,
+            arpeggiator: Default::default(),
+            strum: Default::default(),
+            parameters: Default::default(),
+            extra: Default::default(),
+        }
+    }
+}
+
+impl From<v2::Preset> for Preset {
+    fn from(value: v2::Preset) -> Self {
+        Self {
+            name: value.name,
+            lead_octave: value.lead_octave,
+            guitar_octave: value.guitar_octave,
+            drone_octave: value.drone_octave,
+            pitch: value.pitch,
+            scale: value.scale,
+            drone: DroneSettings {
+                intervals: value.drone.intervals.into(),
+                detune: value.drone.detune,
+                pluck_drone: value.drone.pluck_drone,
+            },
+            mix: value.mix.into(),
+            fx: value.fx.into(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Drone settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct DroneSettings {
+    /// List of notes of the drone
+    #[serde(with = "interval_list_serde")]
+    pub intervals: Vec<Option<Interval>>,
+    /// Detune amount (in midi note) between the notes
+    pub detune: f32,
+    /// Enable the pluck drone
+    pub pluck_drone: bool,
+}
+
+/// Waveforms of the lead voice
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct WaveSettings {
+    /// Amount of sine wave (0-1)
+    pub sine: f32,
+    /// Amount of triangle wave (0-1)
+    pub triangle: f32,
+    /// Amount of square wave (0-1)
+    pub square: f32,
+    /// Portion of the period during which the square wave is high
+    pub pulse_width: f32,
+    /// Amount of sawtooth wave (0-1)
+    pub saw: f32,
+    /// Amount of theremin tone (0-1)
+    pub theremin: f32,
+}
+
+impl Default for WaveSettings {
+    fn default() -> Self {
+        Self {
+            sine: 0.0,
+            triangle: 0.0,
+            square: 0.0,
+            pulse_width: 0.5,
+            saw: 1.0,
+            theremin: 0.0,
+        }
+    }
+}
+
+/// Vibrato of the lead voice
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct VibratoSettings {
+    /// Frequency (Hz)
+    pub rate: f32,
+    /// Largest pitch deviation (semitones)
+    pub depth: f32,
+    /// Time for the vibrato to fade in after a note starts (seconds)
+    pub onset: f32,
+    /// Gesture controlling the depth
+    pub gesture: VibratoGesture,
+}
+
+impl Default for VibratoSettings {
+    fn default() -> Self {
+        Self {
+            rate: 5.5,
+            depth: 0.0,
+            onset: 0.3,
+            gesture: Default::default(),
+        }
+    }
+}
+
+/// Slides between the lead notes
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct GlideSettings {
+    /// Time constant of the slides (seconds), 0 for stepped notes
+    pub time: f32,
+    /// Only slide between connected notes, the new notes starting right away
+    pub legato: bool,
+}
+
+/// Amplitude envelope (ADSR) of the voices
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct EnvelopeSettings {
+    /// Time to reach the full level once the note starts (seconds)
+    pub attack: f32,
+    /// Time to fall to the sustain level (seconds)
+    pub decay: f32,
+    /// Level held while the note plays (0-1)
+    pub sustain: f32,
+    /// Time to fade out once the note stops (seconds)
+    pub release: f32,
+}
+
+impl Default for EnvelopeSettings {
+    fn default() -> Self {
+        Self {
+            attack: 0.01,
+            decay: 0.1,
+            sustain: 1.0,
+            release: 0.05,
+        }
+    }
+}
+
+/// Arpeggiator settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ArpeggiatorSettings {
+    /// Order of the notes, or off to hold the chord
+    pub pattern: ArpPattern,
+    /// Number of steps per beat
+    pub division: u8,
+    /// Portion of the step during which the note is held (0-1)
+    pub gate: f32,
+}
+
+impl Default for ArpeggiatorSettings {
+    fn default() -> Self {
+        Self {
+            pattern: ArpPattern::Off,
+            division: 4,
+            gate: 0.5,
+        }
+    }
+}
+
+/// Guitar strum settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct StrumSettings {
+    /// Delay between two strings of a strum (seconds)
+    pub spacing: f32,
+    /// Quantization of the strums on the tempo
+    pub quantize: TempoSync,
+}
+
+impl Default for StrumSettings {
+    fn default() -> Self {
+        Self {
+            spacing: 0.015,
+            quantize: TempoSync::Free,
+        }
+    }
+}
+
+/// Proxy the interval (de)serialization to flatten it
+mod interval_list_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use staff::Interval;
+
+    #[derive(Serialize, Deserialize)]
+    struct IntervalList(Vec<Option<u8>>);
+
+    impl From<&[Option<Interval>]> for IntervalList {
+        fn from(value: &[Option<Interval>]) -> Self {
+            Self(value.iter().map(|v| v.map(|v| v.semitones())).collect())
+        }
+    }
+
+    impl From<IntervalList> for Vec<Option<Interval>> {
+        fn from(value: IntervalList) -> Self {
+            value.0.into_iter().map(|i| i.map(Interval::new)).collect()
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<Option<Interval>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        IntervalList::deserialize(deserializer).map(|i| i.into())
+    }
+
+    pub fn serialize<S>(intervals: &[Option<Interval>], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        IntervalList::from(intervals).serialize(serializer)
+    }
+}
+
+/// Mix table settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct MixSettings {
+    /// Master volume
+    pub master: Volume,
+    /// Lead synthesizer volume
+    pub lead: Volume,
+    /// Guitar volume
+    pub guitar: Volume,
+    /// Drone volume
+    pub drone: Volume,
+}
+
+impl Default for MixSettings {
+    fn default() -> Self {
+        Self {
+            master: Volume(1.0),
+            lead: Volume(1.0),
+            guitar: Volume(1.0),
+            drone: Volume(0.14),
+        }
+    }
+}
+
+impl From<v1::MixSettings> for MixSettings {
+    fn from(value: v1::MixSettings) -> Self {
+        Self {
+            master: value.master,
+            lead: value.lead,
+            guitar: value.guitar,
+            drone: value.drone,
+        }
+    }
+}
+
+/// Effects settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct FxSettings {
+    /// Echo settings
+    pub echo: EchoSettings,
+    /// Reverb settings
+    pub reverb: ReverbSettings,
+    /// Distortion settings
+    pub distortion: DistortionSettings,
+    /// Equalizer settings
+    pub eq: EqSettings,
+    /// Chorus settings
+    pub chorus: ChorusSettings,
+    /// Phaser settings
+    pub phaser: PhaserSettings,
+}
+
+impl From<v1::FxSettings> for FxSettings {
+    fn from(value: v1::FxSettings) -> Self {
+        Self {
+            echo: value.echo.into(),
+            reverb: value.reverb.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+/// Echo settings
+pub struct EchoSettings {
+    /// Echo amount
+    pub mix: Volume,
+    /// Echo duration (seconds)
+    pub duration: f32,
+    /// Echo feedback (0-1)
+    pub feedback: f32,
+    /// Synchronisation of the echo duration on the tempo
+    pub sync: TempoSync,
+}
+
+impl Default for EchoSettings {
+    fn default() -> Self {
+        Self {
+            mix: Volume(1.0),
+            duration: 0.3,
+            feedback: 0.3,
+            sync: TempoSync::Free,
+        }
+    }
+}
+
+impl From<v1::EchoSettings> for EchoSettings {
+    fn from(value: v1::EchoSettings) -> Self {
+        Self {
+            mix: value.mix,
+            duration: value.duration,
+            feedback: value.feedback,
+            ..Default::default()
+        }
+    }
+}
+
+/// Reverb settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ReverbSettings {
+    /// Reverb amount
+    pub mix: Volume,
+    /// Reverb time
+    pub time: f32,
+    /// Reverb damp amount
+    pub damp: f32,
+    /// Reverb room size
+    pub size: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        Self {
+            mix: Volume(0.11),
+            time: 3.5,
+            damp: 0.88,
+            size: 5.0,
+        }
+    }
+}
+
+impl From<v1::ReverbSettings> for ReverbSettings {
+    fn from(value: v1::ReverbSettings) -> Self {
+        Self {
+            mix: value.mix,
+            time: value.time,
+            damp: value.damp,
+            size: value.size,
+        }
+    }
+}
+
+/// Waveshaping distortion settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct DistortionSettings {
+    /// Distortion amount
+    pub mix: Volume,
+    /// Distortion drive (0-1)
+    pub drive: f32,
+}
+
+impl Default for DistortionSettings {
+    fn default() -> Self {
+        Self {
+            mix: Volume(0.0),
+            drive: 0.5,
+        }
+    }
+}
+
+/// 3-band equalizer settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct EqSettings {
+    /// Low band gain (dB)
+    pub low: f32,
+    /// Mid band gain (dB)
+    pub mid: f32,
+    /// High band gain (dB)
+    pub high: f32,
+}
+
+/// Chorus settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ChorusSettings {
+    /// Chorus amount
+    pub mix: Volume,
+    /// Modulation frequency (Hz)
+    pub rate: f32,
+    /// Modulation depth (0-1)
+    pub depth: f32,
+}
+
+impl Default for ChorusSettings {
+    fn default() -> Self {
+        Self {
+            mix: Volume(0.0),
+            rate: 0.8,
+            depth: 0.5,
+        }
+    }
+}
+
+/// Phaser settings
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PhaserSettings {
+    /// Phaser amount
+    pub mix: Volume,
+    /// Sweep frequency (Hz)
+    pub rate: f32,
+    /// Feedback (0-1)
+    pub feedback: f32,
+}
+
+impl Default for PhaserSettings {
+    fn default() -> Self {
+        Self {
+            mix: Volume(0.0),
+            rate: 0.5,
+            feedback: 0.5,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct System {
+    /// Start theremotion in full screen
+    #[serde(default)]
+    pub fullscreen: bool,
+
+    /// Run tabtip.exe on text input
+    #[serde(default, alias = "tabtip")]
+    pub force_touchscreen: bool,
+
+    /// Bump up the process priority
+    #[serde(default)]
+    pub high_priority_process: bool,
+
+    #[serde(default)]
+    pub handedness: Handedness,
+
+    /// Action triggered by closing the volume hand
+    #[serde(default)]
+    pub fist_action: GestureAction,
+
+    /// Directory of preset files, reloaded when they change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presets_directory: Option<PathBuf>,
+
+    /// Fields written by a newer version, kept when saving
+    #[serde(flatten)]
+    pub extra: serde_yaml::Mapping,
+}
+
+impl From<v1::System> for System {
+    fn from(value: v1::System) -> Self {
+        Self {
+            fullscreen: value.fullscreen,
+            force_touchscreen: value.force_touchscreen,
+            high_priority_process: value.high_priority_process,
+            handedness: value.handedness,
+            ..Default::default()
+        }
+    }
+}
+
+/// Action triggered by a hand gesture
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum GestureAction {
+    /// Do nothing
+    #[default]
+    None,
+    /// Tap the tempo
+    TapTempo,
+    /// Record or overdub a loop
+    Looper,
+    /// Go to the next scene of the setlist
+    NextScene,
+}
+
+/// Scale with a name
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct NamedScale {
+    /// Human readable name
+    pub name: String,
+    /// Corresponding scale
+    pub scale: ScaleIntervals,
+}
+
+impl NamedScale {
+    /// Creates a new [`NamedScale`].
+    pub fn new(name: String, scale: ScaleIntervals) -> Self {
+        Self { name, scale }
+    }
+
+    /// Pseudo hash for identification in the ui
+    pub fn id(&self) -> i32 {
+        let mut hasher = DefaultHasher::default();
+        self.name.hash(&mut hasher);
+        self.scale.bits.hash(&mut hasher);
+        hasher.finish() as i32
+    }
+
+    /// Case insensitive search in the name
+    pub fn matches(&self, search: &str) -> bool {
+        self.name
+            .to_lowercase()
+            .contains(&search.trim().to_lowercase())
+    }
+}
+
+impl From<v1::NamedScale> for NamedScale {
+    fn from(value: v1::NamedScale) -> Self {
+        Self::new(value.name, value.scale)
+    }
+}
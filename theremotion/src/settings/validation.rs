@@ -0,0 +1,418 @@
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use staff::midi::Octave;
+
+use super::{EnvelopeSettings, Preset, Settings};
+use crate::{
+    controls::{Controls, EnvelopeControl},
+    tempo::TEMPO_RANGE,
+    Volume,
+};
+
+/// Accepted octaves of the instruments
+const OCTAVE_RANGE: RangeInclusive<i8> = 0..=4;
+
+/// Accepted volumes
+const VOLUME_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+
+/// Accepted ranges of the preset values, as declared by the DSP
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// Not a DSP parameter, but the echo and the arpeggiator follow it
+    pub tempo: RangeInclusive<f32>,
+    pub echo_duration: RangeInclusive<f32>,
+    pub echo_feedback: RangeInclusive<f32>,
+    pub reverb_time: RangeInclusive<f32>,
+    pub reverb_damp: RangeInclusive<f32>,
+    pub reverb_size: RangeInclusive<f32>,
+    pub distortion_drive: RangeInclusive<f32>,
+    pub eq_gain: RangeInclusive<f32>,
+    pub chorus_rate: RangeInclusive<f32>,
+    pub chorus_depth: RangeInclusive<f32>,
+    pub phaser_rate: RangeInclusive<f32>,
+    pub phaser_feedback: RangeInclusive<f32>,
+    pub drone_detune: RangeInclusive<f32>,
+    pub reference_pitch: RangeInclusive<f32>,
+    pub pulse_width: RangeInclusive<f32>,
+    pub vibrato_rate: RangeInclusive<f32>,
+    pub vibrato_depth: RangeInclusive<f32>,
+    pub vibrato_onset: RangeInclusive<f32>,
+    pub glide_time: RangeInclusive<f32>,
+    pub lead_envelope: EnvelopeLimits,
+    pub drone_envelope: EnvelopeLimits,
+    /// Ranges of the other parameters, by path
+    pub parameters: BTreeMap<String, RangeInclusive<f32>>,
+}
+
+impl From<&Controls> for Limits {
+    fn from(controls: &Controls) -> Self {
+        Self {
+            tempo: TEMPO_RANGE,
+            echo_duration: controls.echo_duration.input.range.clone(),
+            echo_feedback: controls.echo_feedback.input.range.clone(),
+            reverb_time: controls.reverb_time.input.range.clone(),
+            reverb_damp: controls.reverb_damp.input.range.clone(),
+            reverb_size: controls.reverb_size.input.range.clone(),
+            distortion_drive: controls.distortion_drive.input.range.clone(),
+            // The three bands share their range
+            eq_gain: controls.eq_mid.input.range.clone(),
+            chorus_rate: controls.chorus_rate.input.range.clone(),
+            chorus_depth: controls.chorus_depth.input.range.clone(),
+            phaser_rate: controls.phaser_rate.input.range.clone(),
+            phaser_feedback: controls.phaser_feedback.input.range.clone(),
+            drone_detune: controls.drone_detune.input.range.clone(),
+            reference_pitch: controls.reference_pitch.input.range.clone(),
+            pulse_width: controls.wave_pulse_width.input.range.clone(),
+            vibrato_rate: controls.vibrato_rate.input.range.clone(),
+            vibrato_depth: controls.vibrato_depth.input.range.clone(),
+            vibrato_onset: controls.vibrato_onset.input.range.clone(),
+            glide_time: controls.glide_time.input.range.clone(),
+            lead_envelope: (&controls.lead_envelope).into(),
+            drone_envelope: (&controls.drone_envelope).into(),
+            parameters: controls
+                .parameters
+                .iter()
+                .map(|c| (c.path.clone(), c.input.range.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Accepted times of an envelope, its sustain being a volume
+#[derive(Debug, Clone)]
+pub struct EnvelopeLimits {
+    pub attack: RangeInclusive<f32>,
+    pub decay: RangeInclusive<f32>,
+    pub release: RangeInclusive<f32>,
+}
+
+impl From<&EnvelopeControl> for EnvelopeLimits {
+    fn from(control: &EnvelopeControl) -> Self {
+        Self {
+            attack: control.attack.input.range.clone(),
+            decay: control.decay.input.range.clone(),
+            release: control.release.input.range.clone(),
+        }
+    }
+}
+
+/// Bring a value back in its range, noting the correction
+fn clamp(issues: &mut Vec<String>, name: &str, value: &mut f32, range: &RangeInclusive<f32>) {
+    if range.contains(value) {
+        return;
+    }
+    let corrected = if value.is_nan() {
+        *range.start()
+    } else {
+        value.clamp(*range.start(), *range.end())
+    };
+    issues.push(format!(
+        "{name} {value} is out of {}..={}, set to {corrected}",
+        range.start(),
+        range.end()
+    ));
+    *value = corrected;
+}
+
+/// Bring a volume back between 0 and 1, noting the correction
+fn clamp_volume(issues: &mut Vec<String>, name: &str, volume: &mut Volume) {
+    clamp(issues, name, &mut volume.0, &VOLUME_RANGE);
+}
+
+/// Bring the values of an envelope back in their range, noting the corrections
+fn clamp_envelope(
+    issues: &mut Vec<String>,
+    name: &str,
+    envelope: &mut EnvelopeSettings,
+    limits: &EnvelopeLimits,
+) {
+    let i = issues;
+    clamp(
+        i,
+        &format!("{name} attack"),
+        &mut envelope.attack,
+        &limits.attack,
+    );
+    clamp(
+        i,
+        &format!("{name} decay"),
+        &mut envelope.decay,
+        &limits.decay,
+    );
+    clamp(
+        i,
+        &format!("{name} sustain"),
+        &mut envelope.sustain,
+        &VOLUME_RANGE,
+    );
+    clamp(
+        i,
+        &format!("{name} release"),
+        &mut envelope.release,
+        &limits.release,
+    );
+}
+
+/// Bring an octave back in the playable ones, noting the correction
+fn clamp_octave(issues: &mut Vec<String>, name: &str, octave: &mut Octave) {
+    let value = octave.into_i8();
+    if !OCTAVE_RANGE.contains(&value) {
+        let corrected = value.clamp(*OCTAVE_RANGE.start(), *OCTAVE_RANGE.end());
+        issues.push(format!(
+            "{name} {value} is not playable, set to {corrected}"
+        ));
+        *octave = Octave::new_unchecked(corrected);
+    }
+}
+
+impl Preset {
+    /// Correct the values out of their accepted range.
+    ///
+    /// Returns a description of each correction.
+    pub fn validate(&mut self, limits: &Limits) -> Vec<String> {
+        let mut issues = vec![];
+        let i = &mut issues;
+        clamp_octave(i, "Lead octave", &mut self.lead_octave);
+        clamp_octave(i, "Guitar octave", &mut self.guitar_octave);
+        clamp_octave(i, "Drone octave", &mut self.drone_octave);
+        clamp(i, "Sine amount", &mut self.wave.sine, &VOLUME_RANGE);
+        clamp(i, "Triangle amount", &mut self.wave.triangle, &VOLUME_RANGE);
+        clamp(i, "Square amount", &mut self.wave.square, &VOLUME_RANGE);
+        clamp(
+            i,
+            "Pulse width",
+            &mut self.wave.pulse_width,
+            &limits.pulse_width,
+        );
+        clamp(i, "Saw amount", &mut self.wave.saw, &VOLUME_RANGE);
+        clamp(i, "Theremin amount", &mut self.wave.theremin, &VOLUME_RANGE);
+        clamp(
+            i,
+            "Vibrato rate",
+            &mut self.vibrato.rate,
+            &limits.vibrato_rate,
+        );
+        clamp(
+            i,
+            "Vibrato depth",
+            &mut self.vibrato.depth,
+            &limits.vibrato_depth,
+        );
+        clamp(
+            i,
+            "Vibrato onset",
+            &mut self.vibrato.onset,
+            &limits.vibrato_onset,
+        );
+        clamp(i, "Glide time", &mut self.glide.time, &limits.glide_time);
+        clamp_envelope(
+            i,
+            "Lead envelope",
+            &mut self.lead_envelope,
+            &limits.lead_envelope,
+        );
+        clamp_envelope(
+            i,
+            "Drone envelope",
+            &mut self.drone_envelope,
+            &limits.drone_envelope,
+        );
+        clamp_volume(i, "Master volume", &mut self.mix.master);
+        clamp_volume(i, "Lead volume", &mut self.mix.lead);
+        clamp_volume(i, "Guitar volume", &mut self.mix.guitar);
+        clamp_volume(i, "Drone volume", &mut self.mix.drone);
+        clamp(i, "Tempo", &mut self.tempo, &limits.tempo);
+        clamp_volume(i, "Echo mix", &mut self.fx.echo.mix);
+        clamp(
+            i,
+            "Echo duration",
+            &mut self.fx.echo.duration,
+            &limits.echo_duration,
+        );
+        clamp(
+            i,
+            "Echo feedback",
+            &mut self.fx.echo.feedback,
+            &limits.echo_feedback,
+        );
+        clamp_volume(i, "Reverb mix", &mut self.fx.reverb.mix);
+        clamp(
+            i,
+            "Reverb time",
+            &mut self.fx.reverb.time,
+            &limits.reverb_time,
+        );
+        clamp(
+            i,
+            "Reverb damp",
+            &mut self.fx.reverb.damp,
+            &limits.reverb_damp,
+        );
+        clamp(
+            i,
+            "Reverb size",
+            &mut self.fx.reverb.size,
+            &limits.reverb_size,
+        );
+        clamp_volume(i, "Distortion mix", &mut self.fx.distortion.mix);
+        clamp(
+            i,
+            "Distortion drive",
+            &mut self.fx.distortion.drive,
+            &limits.distortion_drive,
+        );
+        clamp(i, "EQ low", &mut self.fx.eq.low, &limits.eq_gain);
+        clamp(i, "EQ mid", &mut self.fx.eq.mid, &limits.eq_gain);
+        clamp(i, "EQ high", &mut self.fx.eq.high, &limits.eq_gain);
+        clamp_volume(i, "Chorus mix", &mut self.fx.chorus.mix);
+        clamp(
+            i,
+            "Chorus rate",
+            &mut self.fx.chorus.rate,
+            &limits.chorus_rate,
+        );
+        clamp(
+            i,
+            "Chorus depth",
+            &mut self.fx.chorus.depth,
+            &limits.chorus_depth,
+        );
+        clamp_volume(i, "Phaser mix", &mut self.fx.phaser.mix);
+        clamp(
+            i,
+            "Phaser rate",
+            &mut self.fx.phaser.rate,
+            &limits.phaser_rate,
+        );
+        clamp(
+            i,
+            "Phaser feedback",
+            &mut self.fx.phaser.feedback,
+            &limits.phaser_feedback,
+        );
+        clamp(
+            i,
+            "Drone detune",
+            &mut self.drone.detune,
+            &limits.drone_detune,
+        );
+        clamp(
+            i,
+            "Reference pitch",
+            &mut self.reference_pitch,
+            &limits.reference_pitch,
+        );
+        // Parameters unknown to this DSP are kept as is
+        for (path, value) in self.parameters.iter_mut() {
+            if let Some(range) = limits.parameters.get(path) {
+                clamp(i, path, value, range);
+            }
+        }
+        issues
+    }
+}
+
+impl Settings {
+    /// Correct the values of the current, user and directory presets out of
+    /// their accepted range.
+    ///
+    /// Returns a description of each correction.
+    pub fn validate(&mut self, limits: &Limits) -> Vec<String> {
+        std::iter::once(&mut self.current_preset)
+            .chain(self.presets.iter_mut())
+            .chain(self.directory_presets.iter_mut())
+            .flat_map(|preset| {
+                let name = preset.name.clone();
+                preset
+                    .validate(limits)
+                    .into_iter()
+                    .map(move |issue| format!("{name}: {issue}"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn limits() -> Limits {
+        Limits {
+            tempo: 40.0..=240.0,
+            echo_duration: 0.01..=3.0,
+            echo_feedback: 0.0..=1.0,
+            reverb_time: 0.1..=60.0,
+            reverb_damp: 0.0..=1.0,
+            reverb_size: 0.5..=5.0,
+            distortion_drive: 0.0..=1.0,
+            eq_gain: -12.0..=12.0,
+            chorus_rate: 0.05..=5.0,
+            chorus_depth: 0.0..=1.0,
+            phaser_rate: 0.05..=10.0,
+            phaser_feedback: 0.0..=0.99,
+            drone_detune: 0.0..=0.3,
+            reference_pitch: 415.0..=466.0,
+            pulse_width: 0.05..=0.95,
+            vibrato_rate: 0.1..=12.0,
+            vibrato_depth: 0.0..=2.0,
+            vibrato_onset: 0.0..=2.0,
+            glide_time: 0.0..=2.0,
+            lead_envelope: EnvelopeLimits {
+                attack: 0.0..=2.0,
+                decay: 0.0..=2.0,
+                release: 0.0..=4.0,
+            },
+            drone_envelope: EnvelopeLimits {
+                attack: 0.0..=4.0,
+                decay: 0.0..=2.0,
+                release: 0.0..=8.0,
+            },
+            parameters: BTreeMap::from([("fx/reverb/mod_freq".to_string(), 0.0..=10.0)]),
+        }
+    }
+
+    #[rstest]
+    fn valid_test() {
+        let mut settings = Settings {
+            presets: Preset::system_presets().to_vec(),
+            ..Default::default()
+        };
+        let before = settings.clone();
+        assert_eq!(Vec::<String>::new(), settings.validate(&limits()));
+        assert_eq!(before, settings);
+    }
+
+    #[rstest]
+    fn invalid_test() {
+        let mut preset = Preset::default();
+        preset.lead_octave = Octave::new_unchecked(9);
+        preset.mix.master = Volume(1.5);
+        preset.fx.echo.duration = f32::NAN;
+        preset.fx.reverb.size = 12.0;
+        preset.reference_pitch = 0.0;
+        preset.wave.pulse_width = 1.0;
+        preset.drone_envelope.release = -1.0;
+        preset.fx.eq.low = 20.0;
+        preset.tempo = 0.0;
+        preset
+            .parameters
+            .insert("fx/reverb/mod_freq".to_string(), 20.0);
+        preset.parameters.insert("removed".to_string(), 20.0);
+
+        let issues = preset.validate(&limits());
+        assert_eq!(10, issues.len());
+        assert_eq!(4, preset.lead_octave.into_i8());
+        assert_eq!(Volume(1.0), preset.mix.master);
+        assert_eq!(0.01, preset.fx.echo.duration);
+        assert_eq!(5.0, preset.fx.reverb.size);
+        assert_eq!(415.0, preset.reference_pitch);
+        assert_eq!(0.95, preset.wave.pulse_width);
+        assert_eq!(0.0, preset.drone_envelope.release);
+        assert_eq!(12.0, preset.fx.eq.low);
+        assert_eq!(40.0, preset.tempo);
+        assert_eq!(Some(&10.0), preset.parameters.get("fx/reverb/mod_freq"));
+        assert_eq!(Some(&20.0), preset.parameters.get("removed"));
+    }
+}
@@ -1,5 +1,11 @@
+mod arpeggiator;
+mod edo;
 mod scale_windows;
 mod scales;
+mod tuning;
 
+pub use arpeggiator::*;
+pub use edo::*;
 pub use scale_windows::*;
 pub use scales::*;
+pub use tuning::*;
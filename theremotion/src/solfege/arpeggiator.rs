@@ -0,0 +1,225 @@
+use serde::{Deserialize, Serialize};
+
+use crate::MidiNoteF;
+
+/// Order in which the arpeggiator plays the chord notes
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArpPattern {
+    /// No arpeggiator, the chord notes are held together
+    #[default]
+    Off,
+    /// From the lowest to the highest note
+    Up,
+    /// From the highest to the lowest note
+    Down,
+    /// Up then down, without repeating the extreme notes
+    UpDown,
+    /// Random note at each step
+    Random,
+    /// Notes in the order they entered the chord
+    AsPlayed,
+}
+
+/// Sequencer playing the notes of a chord one at a time
+#[derive(Debug, Default)]
+pub struct Arpeggiator {
+    /// Last computed step and the voice it plays
+    current: Option<(u64, Option<usize>)>,
+    /// Rounded notes, in the order they entered the chord
+    played: Vec<i32>,
+    /// State of the pseudo random generator
+    seed: u32,
+}
+
+impl Arpeggiator {
+    /// Restart the sequence on the next call
+    pub fn reset(&mut self) {
+        self.current = None;
+        self.played.clear();
+    }
+
+    /// Find the chord voice to play at the given position.
+    ///
+    /// `position` is the number of steps elapsed on the tempo clock, its
+    /// fractional part being the progress in the current step. `density` is
+    /// the number of notes played every `chord.len()` steps, the other steps
+    /// are rests. Returns `None` during rests and once the gate portion of
+    /// the step is elapsed.
+    pub fn voice(
+        &mut self,
+        pattern: ArpPattern,
+        chord: &[MidiNoteF],
+        density: usize,
+        position: f32,
+        gate: f32,
+    ) -> Option<usize> {
+        self.update_played(chord);
+        let steps = chord.len();
+        if steps == 0 || !position.is_finite() || position < 0.0 {
+            return None;
+        }
+
+        let index = position as u64;
+
+        let voice = match self.current {
+            Some((current, voice)) if current == index => voice,
+            _ => {
+                let density = density.clamp(1, steps);
+                let voice = is_hit(index, density, steps)
+                    .then(|| self.pattern_voice(pattern, hits_before(index, density, steps), chord))
+                    .flatten();
+                self.current = Some((index, voice));
+                voice
+            }
+        };
+
+        if position.fract() < gate {
+            voice
+        } else {
+            None
+        }
+    }
+
+    /// Voice played by the nth note of the pattern
+    fn pattern_voice(
+        &mut self,
+        pattern: ArpPattern,
+        hit: u64,
+        chord: &[MidiNoteF],
+    ) -> Option<usize> {
+        let n = chord.len() as u64;
+        match pattern {
+            ArpPattern::Off => None,
+            ArpPattern::Up => Some((hit % n) as usize),
+            ArpPattern::Down => Some((n - 1 - hit % n) as usize),
+            ArpPattern::UpDown => {
+                if n == 1 {
+                    return Some(0);
+                }
+                let period = 2 * n - 2;
+                let position = hit % period;
+                Some(if position < n {
+                    position
+                } else {
+                    period - position
+                } as usize)
+            }
+            ArpPattern::Random => Some((self.next_random() % n as u32) as usize),
+            ArpPattern::AsPlayed => {
+                let note = *self.played.get((hit % self.played.len() as u64) as usize)?;
+                chord.iter().position(|c| c.round().note() as i32 == note)
+            }
+        }
+    }
+
+    /// Keep track of the order in which the notes entered the chord
+    fn update_played(&mut self, chord: &[MidiNoteF]) {
+        let notes: Vec<i32> = chord.iter().map(|n| n.round().note() as i32).collect();
+        self.played.retain(|n| notes.contains(n));
+        for note in notes {
+            if !self.played.contains(&note) {
+                self.played.push(note);
+            }
+        }
+    }
+
+    /// Xorshift pseudo random generator, good enough for musical purpose
+    fn next_random(&mut self) -> u32 {
+        if self.seed == 0 {
+            self.seed = 0x9E37_79B9;
+        }
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed
+    }
+}
+
+/// Evenly distribute `density` notes in every `steps` steps (euclidean rhythm)
+fn is_hit(step: u64, density: usize, steps: usize) -> bool {
+    (step * density as u64) % (steps as u64) < density as u64
+}
+
+/// Number of notes played before the given step
+fn hits_before(step: u64, density: usize, steps: usize) -> u64 {
+    let steps_u64 = steps as u64;
+    let full_cycles = (step / steps_u64) * density as u64;
+    let remaining = (0..step % steps_u64)
+        .filter(|s| is_hit(*s, density, steps))
+        .count() as u64;
+    full_cycles + remaining
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(4, 4, vec![true, true, true, true])]
+    #[case(2, 4, vec![true, false, true, false])]
+    #[case(1, 4, vec![true, false, false, false])]
+    #[case(2, 3, vec![true, false, true])]
+    fn is_hit_test(#[case] density: usize, #[case] steps: usize, #[case] expected: Vec<bool>) {
+        let hits: Vec<bool> = (0..steps as u64)
+            .map(|s| is_hit(s, density, steps))
+            .collect();
+        assert_eq!(expected, hits);
+    }
+
+    #[rstest]
+    #[case(ArpPattern::Up, vec![0, 1, 2, 0, 1, 2])]
+    #[case(ArpPattern::Down, vec![2, 1, 0, 2, 1, 0])]
+    #[case(ArpPattern::UpDown, vec![0, 1, 2, 1, 0, 1])]
+    fn pattern_test(#[case] pattern: ArpPattern, #[case] expected: Vec<usize>) {
+        let chord = [MidiNoteF(60.0), MidiNoteF(64.0), MidiNoteF(67.0)];
+        let mut arpeggiator = Arpeggiator::default();
+        let voices: Vec<usize> = (0..expected.len())
+            .filter_map(|i| arpeggiator.voice(pattern, &chord, 3, i as f32 + 0.25, 0.5))
+            .collect();
+        assert_eq!(expected, voices);
+    }
+
+    #[rstest]
+    #[case(0.25, Some(0))]
+    #[case(4.25, Some(1))]
+    #[case(8.1, Some(2))]
+    fn position_test(#[case] position: f32, #[case] expected: Option<usize>) {
+        // The step comes from the clock, not from the first call
+        let chord = [MidiNoteF(60.0), MidiNoteF(64.0), MidiNoteF(67.0)];
+        let mut arpeggiator = Arpeggiator::default();
+        assert_eq!(
+            expected,
+            arpeggiator.voice(ArpPattern::Up, &chord, 3, position, 0.5)
+        );
+    }
+
+    #[rstest]
+    fn as_played_test() {
+        let mut arpeggiator = Arpeggiator::default();
+        arpeggiator.update_played(&[MidiNoteF(60.0), MidiNoteF(64.0)]);
+        arpeggiator.update_played(&[MidiNoteF(64.0), MidiNoteF(67.0)]);
+        let chord = [MidiNoteF(62.0), MidiNoteF(64.0), MidiNoteF(67.0)];
+        assert_eq!(
+            Some(1),
+            arpeggiator.pattern_voice(ArpPattern::AsPlayed, 0, &chord)
+        );
+        arpeggiator.update_played(&chord);
+        assert_eq!(
+            Some(0),
+            arpeggiator.pattern_voice(ArpPattern::AsPlayed, 2, &chord)
+        );
+    }
+
+    #[rstest]
+    fn gate_test() {
+        let chord = [MidiNoteF(60.0), MidiNoteF(64.0)];
+        let mut arpeggiator = Arpeggiator::default();
+        assert_eq!(
+            Some(0),
+            arpeggiator.voice(ArpPattern::Up, &chord, 2, 0.0, 0.5)
+        );
+        assert_eq!(None, arpeggiator.voice(ArpPattern::Up, &chord, 2, 0.6, 0.5));
+    }
+}
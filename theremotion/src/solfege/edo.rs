@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use staff::scale::ScaleIntervals;
+
+use super::Tuning;
+
+/// Accepted numbers of divisions of the octave
+pub const EDO_DIVISIONS: std::ops::RangeInclusive<u8> = 5..=72;
+
+/// Scale in an equal division of the octave (EDO)
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EdoScale {
+    /// Number of equal steps in the octave
+    pub divisions: u8,
+    /// Steps of the scale from the root, in order and without the octave
+    pub degrees: Vec<u8>,
+}
+
+impl Default for EdoScale {
+    fn default() -> Self {
+        Self::chromatic(12)
+    }
+}
+
+impl EdoScale {
+    /// All the steps of the division
+    pub fn chromatic(divisions: u8) -> Self {
+        Self {
+            divisions,
+            degrees: (0..divisions).collect(),
+        }
+    }
+
+    /// Closest approximation of a 12-TET scale in the given division
+    pub fn from_scale(scale: ScaleIntervals, divisions: u8) -> Self {
+        let mut degrees: Vec<u8> = scale
+            .map(|interval| {
+                let step = (interval.semitones() as f32 * divisions as f32 / 12.0).round() as u8;
+                step % divisions.max(1)
+            })
+            .collect();
+        degrees.sort_unstable();
+        degrees.dedup();
+        Self { divisions, degrees }
+    }
+
+    /// Size of a step in cents
+    pub fn step_cents(&self) -> f32 {
+        1200.0 / self.divisions.max(1) as f32
+    }
+
+    /// Add or remove a step of the scale. The root can't be removed.
+    pub fn toggle(&mut self, degree: u8) {
+        if degree == 0 || degree >= self.divisions {
+            return;
+        }
+        match self.degrees.binary_search(&degree) {
+            Ok(index) => {
+                self.degrees.remove(index);
+            }
+            Err(index) => self.degrees.insert(index, degree),
+        }
+    }
+
+    /// Whether a step is part of the scale
+    pub fn contains(&self, degree: u8) -> bool {
+        self.degrees.contains(&degree)
+    }
+}
+
+impl From<&EdoScale> for Tuning {
+    fn from(value: &EdoScale) -> Self {
+        let step = value.step_cents();
+        Self {
+            description: format!("{}-EDO", value.divisions),
+            pitches: value
+                .degrees
+                .iter()
+                .filter(|degree| **degree != 0)
+                .map(|degree| *degree as f32 * step)
+                .chain(std::iter::once(1200.0))
+                .collect(),
+            keyboard_mapping: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::MidiNoteF;
+
+    #[rstest]
+    #[case(12, vec![0, 2, 4, 5, 7, 9, 11])]
+    #[case(24, vec![0, 4, 8, 10, 14, 18, 22])]
+    #[case(19, vec![0, 3, 6, 8, 11, 14, 17])]
+    fn from_scale_test(#[case] divisions: u8, #[case] expected: Vec<u8>) {
+        let scale = EdoScale::from_scale(ScaleIntervals::major(), divisions);
+        assert_eq!(expected, scale.degrees);
+    }
+
+    #[rstest]
+    fn toggle_test() {
+        let mut scale = EdoScale {
+            divisions: 24,
+            degrees: vec![0, 4, 8],
+        };
+        scale.toggle(7);
+        assert_eq!(vec![0, 4, 7, 8], scale.degrees);
+        scale.toggle(8);
+        assert_eq!(vec![0, 4, 7], scale.degrees);
+        // The root stays
+        scale.toggle(0);
+        scale.toggle(24);
+        assert_eq!(vec![0, 4, 7], scale.degrees);
+    }
+
+    #[rstest]
+    fn tuning_test() {
+        let scale = EdoScale {
+            divisions: 24,
+            degrees: vec![0, 3, 7],
+        };
+        let tuning = Tuning::from(&scale);
+        assert_eq!("24-EDO", tuning.description);
+        assert_eq!(vec![150.0, 350.0, 1200.0], tuning.pitches);
+        let notes = tuning.notes(MidiNoteF(60.0), MidiNoteF(60.0)..=MidiNoteF(72.0));
+        assert_eq!(
+            vec![
+                MidiNoteF(60.0),
+                MidiNoteF(61.5),
+                MidiNoteF(63.5),
+                MidiNoteF(72.0)
+            ],
+            notes
+        );
+    }
+}
@@ -17,15 +17,9 @@ impl ScaleWindows {
         Self { windows }
     }
 
-    /// Initialize from a list of midi notes
-    pub fn from_notes(notes: Vec<MidiNote>) -> Self {
-        Self::new(
-            notes
-                .into_iter()
-                .map(MidiNoteF::from)
-                .tuple_windows()
-                .collect(),
-        )
+    /// Initialize from an ordered list of notes
+    pub fn from_notes(notes: impl IntoIterator<Item = impl Into<MidiNoteF>>) -> Self {
+        Self::new(notes.into_iter().map(Into::into).tuple_windows().collect())
     }
 
     /// Find the position of the two neighbours surrounding the given note.
@@ -89,12 +83,11 @@ impl ScaleWindows {
 
     /// Given a note and a list of degree, output a chord in the scale.
     /// The input note can slide between notes, creating a sliding chord.
-    pub fn autochord<const N: usize>(
-        &self,
-        note: MidiNoteF,
-        degrees: &[isize; N],
-    ) -> [Option<MidiNoteF>; N] {
-        degrees.map(|degree| self.autodegree(note, degree))
+    pub fn autochord(&self, note: MidiNoteF, degrees: &[isize]) -> Vec<Option<MidiNoteF>> {
+        degrees
+            .iter()
+            .map(|degree| self.autodegree(note, *degree))
+            .collect()
     }
 
     /// Configurable autotune of an input note
@@ -130,6 +123,15 @@ pub fn build_scale_notes(
         .collect()
 }
 
+/// Scale degrees of a chord with the given number of voices.
+///
+/// The triad is stacked on each octave: 0, 2, 4, 7, 9, 11, 14...
+pub fn chord_degrees(voices: usize) -> Vec<isize> {
+    (0..voices as isize)
+        .map(|i| (i / 3) * 7 + (i % 3) * 2)
+        .collect()
+}
+
 /// Smooth step function loosely "sticking" the value to 0 or 1
 /// Assumes that value is between 0 and 1
 /// <https://en.wikipedia.org/wiki/Smoothstep>
@@ -191,4 +193,12 @@ mod tests {
         let scale = ScaleWindows::from_notes(notes);
         assert_eq!(Some(expected), scale.autodegree(note.into(), degree));
     }
+
+    #[rstest]
+    #[case(1, vec![0])]
+    #[case(4, vec![0, 2, 4, 7])]
+    #[case(6, vec![0, 2, 4, 7, 9, 11])]
+    fn chord_degrees_test(#[case] voices: usize, #[case] expected: Vec<isize>) {
+        assert_eq!(expected, chord_degrees(voices));
+    }
 }
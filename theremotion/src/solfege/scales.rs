@@ -1,36 +1,69 @@
+use serde::Deserialize;
 use staff::{scale::ScaleIntervals, Interval};
 
-/// More predefined scales
-pub trait MoreScales {
-    /// <https://en.wikipedia.org/wiki/Phrygian_dominant_scale>
-    fn freygish() -> Self;
+/// Built-in scales
+const CATALOG_BYTES: &[u8] = include_bytes!("scales.yaml");
 
-    /// <https://en.wikipedia.org/wiki/Ukrainian_Dorian_scale>
-    fn altered_dorian() -> Self;
+lazy_static::lazy_static! {
+    /// Built-in scales, by category
+    static ref CATALOG: Vec<ScaleCategory> = serde_yaml::from_slice(CATALOG_BYTES).unwrap();
 }
 
-impl MoreScales for ScaleIntervals {
-    fn freygish() -> Self {
-        ScaleIntervals::from_iter([
-            Interval::UNISON,
-            Interval::MINOR_SECOND,
-            Interval::MAJOR_THIRD,
-            Interval::PERFECT_FOURTH,
-            Interval::PERFECT_FIFTH,
-            Interval::MINOR_SIXTH,
-            Interval::MINOR_SEVENTH,
-        ])
+/// Category of built-in scales
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScaleCategory {
+    /// Human readable name
+    pub name: String,
+    /// Scales of the category
+    pub scales: Vec<CatalogScale>,
+}
+
+/// Built-in scale
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogScale {
+    /// Human readable name
+    pub name: String,
+    /// Semitones from the root
+    pub intervals: Vec<u8>,
+}
+
+impl CatalogScale {
+    /// Corresponding scale
+    pub fn scale(&self) -> ScaleIntervals {
+        ScaleIntervals::from_iter(self.intervals.iter().map(|i| Interval::new(i % 12)))
     }
+}
+
+/// Built-in scales, by category
+pub fn scale_catalog() -> &'static [ScaleCategory] {
+    &CATALOG
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rstest::rstest;
+
+    use super::*;
 
-    fn altered_dorian() -> Self {
-        Self::from_iter([
-            Interval::UNISON,
-            Interval::MAJOR_SECOND,
-            Interval::MINOR_THIRD,
-            Interval::TRITONE,
-            Interval::PERFECT_FIFTH,
-            Interval::MAJOR_SIXTH,
-            Interval::MINOR_SEVENTH,
-        ])
+    #[rstest]
+    fn catalog_test() {
+        let catalog = scale_catalog();
+        assert!(catalog.len() > 1);
+        let mut names = HashSet::new();
+        let mut intervals = HashSet::new();
+        for scale in catalog.iter().flat_map(|c| &c.scales) {
+            assert!(names.insert(&scale.name), "Duplicate scale {}", scale.name);
+            assert!(
+                intervals.insert(&scale.intervals),
+                "Duplicate intervals {}",
+                scale.name
+            );
+            assert_eq!(Some(&0), scale.intervals.first(), "{}", scale.name);
+            assert!(scale.intervals.iter().all(|i| *i < 12), "{}", scale.name);
+        }
     }
 }
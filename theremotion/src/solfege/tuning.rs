@@ -0,0 +1,380 @@
+use std::{ops::RangeInclusive, path::Path};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::MidiNoteF;
+
+/// Microtonal tuning, following the Scala scale format
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Tuning {
+    /// Human readable description
+    pub description: String,
+    /// Pitch of each degree from the root in cents, the last one being the period
+    pub pitches: Vec<f32>,
+    /// Keyboard mapping, the scale starts on the root note when missing
+    pub keyboard_mapping: Option<KeyboardMapping>,
+}
+
+/// Mapping of the scale on the MIDI notes, following the Scala keyboard mapping format
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct KeyboardMapping {
+    /// First mapped MIDI note
+    pub first: u8,
+    /// Last mapped MIDI note
+    pub last: u8,
+    /// MIDI note of the first degree
+    pub middle: u8,
+    /// MIDI note tuned on the reference frequency
+    pub reference: u8,
+    /// Frequency of the reference note (Hz)
+    pub frequency: f32,
+    /// Degree repeating the mapping, the scale period when 0
+    pub octave_degree: usize,
+    /// Degree of each note of the repeating pattern, all the degrees in order when empty
+    pub mapping: Vec<Option<usize>>,
+}
+
+/// Scala file loaded by the user
+#[derive(Clone, Debug, PartialEq)]
+pub enum TuningFile {
+    /// Scale (.scl), replacing the tuning
+    Scale(Tuning),
+    /// Keyboard mapping (.kbm), applied on the current tuning
+    KeyboardMapping(KeyboardMapping),
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            description: "12-TET".to_string(),
+            pitches: (1..=12).map(|i| i as f32 * 100.0).collect(),
+            keyboard_mapping: None,
+        }
+    }
+}
+
+impl Default for KeyboardMapping {
+    fn default() -> Self {
+        Self {
+            first: 0,
+            last: 127,
+            middle: 60,
+            reference: 69,
+            frequency: 440.0,
+            octave_degree: 0,
+            mapping: vec![],
+        }
+    }
+}
+
+/// Lines of a Scala file, without the comments
+fn scala_lines(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('!'))
+        .map(|line| line.trim())
+}
+
+/// First word of a line, the rest is ignored in the Scala files
+fn first_word(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or_default()
+}
+
+/// Parse a Scala pitch: cents when it has a dot, ratio otherwise
+fn parse_pitch(value: &str) -> Result<f32> {
+    if value.contains('.') {
+        return Ok(value.parse()?);
+    }
+    let (numerator, denominator) = value.split_once('/').unwrap_or((value, "1"));
+    let ratio = numerator.parse::<f32>()? / denominator.parse::<f32>()?;
+    if ratio <= 0.0 {
+        bail!("Invalid ratio {}", value);
+    }
+    Ok(1200.0 * ratio.log2())
+}
+
+impl Tuning {
+    /// Parse a Scala scale file (.scl)
+    pub fn from_scl(content: &str) -> Result<Self> {
+        let mut lines = scala_lines(content);
+        let description = lines.next().context("Missing description")?.to_string();
+        let count: usize = first_word(lines.next().context("Missing note count")?).parse()?;
+        let pitches = lines
+            .filter(|line| !line.is_empty())
+            .take(count)
+            .map(|line| {
+                parse_pitch(first_word(line)).with_context(|| format!("Invalid pitch {line}"))
+            })
+            .collect::<Result<Vec<f32>>>()?;
+        if pitches.len() != count {
+            bail!("Expected {} pitches, found {}", count, pitches.len());
+        }
+        if pitches.last().copied().unwrap_or_default() <= 0.0 {
+            bail!("The period of the scale must be positive");
+        }
+        Ok(Self {
+            description,
+            pitches,
+            keyboard_mapping: None,
+        })
+    }
+
+    /// Pitch of a degree from the root in cents, repeating the scale on the period
+    pub fn degree_cents(&self, degree: i32) -> f32 {
+        let count = self.pitches.len() as i32;
+        let period = self.pitches.last().copied().unwrap_or(1200.0);
+        let index = degree.rem_euclid(count);
+        let octave = degree.div_euclid(count);
+        let pitch = if index == 0 {
+            0.0
+        } else {
+            self.pitches[(index - 1) as usize]
+        };
+        octave as f32 * period + pitch
+    }
+
+    /// List the notes of the tuning in a range, starting the scale on the root
+    /// note when there is no keyboard mapping
+    pub fn notes(&self, root: MidiNoteF, range: RangeInclusive<MidiNoteF>) -> Vec<MidiNoteF> {
+        if self.pitches.is_empty() {
+            return vec![];
+        }
+        let mut notes = match &self.keyboard_mapping {
+            Some(mapping) => self.mapped_notes(mapping),
+            None => {
+                let count = self.pitches.len() as i32;
+                let period = self.degree_cents(count) / 100.0;
+                let first = ((range.start().note() - root.note()) / period).floor() as i32;
+                let last = ((range.end().note() - root.note()) / period).ceil() as i32;
+                (first * count..=last * count)
+                    .map(|degree| MidiNoteF(root.note() + self.degree_cents(degree) / 100.0))
+                    .collect()
+            }
+        };
+        notes.retain(|note| range.contains(note));
+        notes.sort_by(|a, b| a.note().total_cmp(&b.note()));
+        notes.dedup();
+        notes
+    }
+
+    /// Notes of the tuning following a keyboard mapping
+    fn mapped_notes(&self, mapping: &KeyboardMapping) -> Vec<MidiNoteF> {
+        let key_cents = |key: u8| mapping.key_cents(self, key);
+        let reference_cents = key_cents(mapping.reference)
+            .unwrap_or(100.0 * (mapping.reference as f32 - mapping.middle as f32));
+        let reference_note = 69.0 + 12.0 * (mapping.frequency / 440.0).log2();
+        let middle_note = reference_note - reference_cents / 100.0;
+        (mapping.first..=mapping.last)
+            .filter_map(|key| key_cents(key).map(|cents| MidiNoteF(middle_note + cents / 100.0)))
+            .collect()
+    }
+}
+
+impl TuningFile {
+    /// Read a Scala scale, or a keyboard mapping for the .kbm files
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let is_kbm = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("kbm"))
+            .unwrap_or(false);
+        if is_kbm {
+            Ok(Self::KeyboardMapping(KeyboardMapping::from_kbm(&content)?))
+        } else {
+            Ok(Self::Scale(Tuning::from_scl(&content)?))
+        }
+    }
+
+    /// Tuning resulting from the file loaded over the current one
+    pub fn apply(self, current: Option<Tuning>) -> Tuning {
+        match self {
+            Self::Scale(tuning) => tuning,
+            Self::KeyboardMapping(mapping) => Tuning {
+                keyboard_mapping: Some(mapping),
+                ..current.unwrap_or_default()
+            },
+        }
+    }
+}
+
+impl KeyboardMapping {
+    /// Parse a Scala keyboard mapping file (.kbm)
+    pub fn from_kbm(content: &str) -> Result<Self> {
+        let mut lines = scala_lines(content).filter(|line| !line.is_empty());
+        let mut next = |name: &str| {
+            lines
+                .next()
+                .map(first_word)
+                .with_context(|| format!("Missing {name}"))
+        };
+        let size: usize = next("map size")?.parse()?;
+        let first = next("first note")?.parse()?;
+        let last = next("last note")?.parse()?;
+        let middle = next("middle note")?.parse()?;
+        let reference = next("reference note")?.parse()?;
+        let frequency = next("reference frequency")?.parse()?;
+        let octave_degree = next("octave degree")?.parse()?;
+        let mut mapping = Vec::with_capacity(size);
+        for _ in 0..size {
+            // Missing entries are unmapped
+            let degree = match next("mapping") {
+                Ok("x") | Err(_) => None,
+                Ok(degree) => Some(degree.parse()?),
+            };
+            mapping.push(degree);
+        }
+        Ok(Self {
+            first,
+            last,
+            middle,
+            reference,
+            frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    /// Pitch of a MIDI note from the middle note in cents, if mapped
+    fn key_cents(&self, tuning: &Tuning, key: u8) -> Option<f32> {
+        let offset = key as i32 - self.middle as i32;
+        if self.mapping.is_empty() {
+            return Some(tuning.degree_cents(offset));
+        }
+        let size = self.mapping.len() as i32;
+        let octave_degree = if self.octave_degree == 0 {
+            tuning.pitches.len()
+        } else {
+            self.octave_degree
+        };
+        let degree = (*self.mapping.get(offset.rem_euclid(size) as usize)?)?;
+        Some(
+            offset.div_euclid(size) as f32 * tuning.degree_cents(octave_degree as i32)
+                + tuning.degree_cents(degree as i32),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    const JUST: &str = "! just.scl
+!
+Just intonation
+ 4
+!
+ 9/8
+ 5/4 major third
+ 701.955
+ 2
+";
+
+    #[rstest]
+    fn from_scl_test() {
+        let tuning = Tuning::from_scl(JUST).unwrap();
+        assert_eq!("Just intonation", tuning.description);
+        assert_eq!(4, tuning.pitches.len());
+        assert!((tuning.pitches[0] - 203.91).abs() < 0.01);
+        assert!((tuning.pitches[1] - 386.31).abs() < 0.01);
+        assert!((tuning.pitches[2] - 701.955).abs() < 0.01);
+        assert!((tuning.pitches[3] - 1200.0).abs() < 0.01);
+    }
+
+    #[rstest]
+    #[case("Bad\n2\n100.0\n")]
+    #[case("Bad\n1\nfoo\n")]
+    #[case("Bad\n1\n-1.0\n")]
+    fn from_scl_error_test(#[case] content: &str) {
+        assert!(Tuning::from_scl(content).is_err());
+    }
+
+    #[rstest]
+    fn twelve_tet_test() {
+        let notes = Tuning::default().notes(MidiNoteF(0.0), MidiNoteF(60.0)..=MidiNoteF(72.0));
+        let expected: Vec<MidiNoteF> = (60..=72).map(|n| MidiNoteF(n as f32)).collect();
+        assert_eq!(expected.len(), notes.len());
+        for (expected, note) in expected.iter().zip(notes) {
+            assert!((expected.note() - note.note()).abs() < 0.001);
+        }
+    }
+
+    #[rstest]
+    fn notes_test() {
+        let tuning = Tuning::from_scl(JUST).unwrap();
+        let notes = tuning.notes(MidiNoteF(2.0), MidiNoteF(60.0)..=MidiNoteF(75.0));
+        let expected = [62.0, 64.0391, 65.8631, 69.0196, 74.0];
+        assert_eq!(expected.len(), notes.len());
+        for (expected, note) in expected.iter().zip(notes) {
+            assert!((expected - note.note()).abs() < 0.001);
+        }
+    }
+
+    #[rstest]
+    fn from_kbm_test() {
+        let mapping = KeyboardMapping::from_kbm(
+            "! 432.kbm
+5
+0
+127
+60
+69
+432.0
+4
+0
+x
+1
+2
+3
+",
+        )
+        .unwrap();
+        assert_eq!(60, mapping.middle);
+        assert_eq!(432.0, mapping.frequency);
+        assert_eq!(
+            vec![Some(0), None, Some(1), Some(2), Some(3)],
+            mapping.mapping
+        );
+    }
+
+    #[rstest]
+    fn mapped_notes_test() {
+        let tuning = Tuning {
+            keyboard_mapping: Some(KeyboardMapping {
+                frequency: 432.0,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let notes = tuning.notes(MidiNoteF(0.0), MidiNoteF(68.5)..=MidiNoteF(69.5));
+        assert_eq!(1, notes.len());
+        // 432Hz is about 31.8 cents below 440Hz
+        assert!((notes[0].note() - 68.682).abs() < 0.001);
+    }
+
+    #[rstest]
+    fn apply_test() {
+        let just = Tuning::from_scl(JUST).unwrap();
+        let mapping = KeyboardMapping {
+            middle: 62,
+            ..Default::default()
+        };
+
+        // The keyboard mapping keeps the current scale
+        let tuning = TuningFile::KeyboardMapping(mapping.clone()).apply(Some(just.clone()));
+        assert_eq!(just.pitches, tuning.pitches);
+        assert_eq!(Some(mapping.clone()), tuning.keyboard_mapping);
+        let tuning = TuningFile::KeyboardMapping(mapping).apply(None);
+        assert_eq!(Tuning::default().pitches, tuning.pitches);
+
+        assert_eq!(
+            just,
+            TuningFile::Scale(just.clone()).apply(Some(Tuning::default()))
+        );
+    }
+}
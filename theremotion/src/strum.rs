@@ -0,0 +1,249 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Hand rotation at which the strings are strummed
+const STRUM_ANGLE: f32 = std::f32::consts::FRAC_PI_2 + 0.3;
+
+/// Rotation to come back before strumming again in the other direction
+const STRUM_HYSTERESIS: f32 = 0.1;
+
+/// Angular speed (rad/s) giving the full velocity
+const MAX_SPEED: f32 = 15.0;
+
+/// Minimum velocity of a strum
+const MIN_VELOCITY: f32 = 0.05;
+
+/// Time a string gate is held, long enough to be seen by the DSP
+const GATE_DURATION: Duration = Duration::from_millis(20);
+
+/// Direction of a strum
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrumDirection {
+    /// From the lowest to the highest string
+    Down,
+    /// From the highest to the lowest string
+    Up,
+}
+
+/// Strum detected from the hand rotation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Strum {
+    /// Direction of the strum
+    pub direction: StrumDirection,
+    /// Strength of the strum (0-1)
+    pub velocity: f32,
+}
+
+/// Change of a string gate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StringEvent {
+    /// Pluck the string with the given velocity
+    Pluck(usize, f32),
+    /// Release the string gate
+    Release(usize),
+}
+
+/// Pluck waiting to be played
+#[derive(Debug, Clone, Copy)]
+struct Pluck {
+    string: usize,
+    at: Instant,
+    velocity: f32,
+}
+
+/// Strum engine, turning the hand rotation into plucks scheduled across
+/// the strings
+#[derive(Debug, Default)]
+pub struct StrumEngine {
+    /// The hand is rotated past the strum angle
+    past: bool,
+    /// Last rotation and its time, to compute the angular speed
+    last_rotation: Option<(f32, Instant)>,
+    /// Plucks waiting to be played
+    scheduled: Vec<Pluck>,
+    /// Gate state of the strings and time of their last change
+    gates: HashMap<usize, (bool, Instant)>,
+}
+
+impl StrumEngine {
+    /// Follow the hand rotation, and detect the strums when `ready`
+    pub fn detect(&mut self, rotation: f32, ready: bool, now: Instant) -> Option<Strum> {
+        let speed = match self.last_rotation {
+            Some((last, time)) if now > time => {
+                (rotation - last).abs() / (now - time).as_secs_f32()
+            }
+            _ => 0.0,
+        };
+        self.last_rotation = Some((rotation, now));
+
+        let direction = if !self.past && rotation > STRUM_ANGLE + STRUM_HYSTERESIS {
+            self.past = true;
+            StrumDirection::Down
+        } else if self.past && rotation < STRUM_ANGLE - STRUM_HYSTERESIS {
+            self.past = false;
+            StrumDirection::Up
+        } else {
+            return None;
+        };
+
+        ready.then(|| Strum {
+            direction,
+            velocity: (speed / MAX_SPEED).clamp(MIN_VELOCITY, 1.0),
+        })
+    }
+
+    /// Schedule the plucks of a strum, from `start` and separated by `spacing`.
+    ///
+    /// The strings are ordered from the lowest to the highest.
+    pub fn schedule(&mut self, strum: Strum, strings: &[usize], spacing: Duration, start: Instant) {
+        let ordered: Vec<usize> = match strum.direction {
+            StrumDirection::Down => strings.to_vec(),
+            StrumDirection::Up => strings.iter().rev().copied().collect(),
+        };
+        for (i, string) in ordered.into_iter().enumerate() {
+            self.scheduled.push(Pluck {
+                string,
+                at: start + spacing * i as u32,
+                velocity: strum.velocity,
+            });
+        }
+    }
+
+    /// Earliest instant at which a gate change may be due, if any
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let releases = self
+            .gates
+            .values()
+            .filter(|(gate, _)| *gate)
+            .map(|(_, changed)| *changed + GATE_DURATION);
+        let plucks = self.scheduled.iter().map(|pluck| {
+            // A string gate must stay released for a while to be plucked again
+            self.gates
+                .get(&pluck.string)
+                .map(|(_, changed)| pluck.at.max(*changed + GATE_DURATION))
+                .unwrap_or(pluck.at)
+        });
+        releases.chain(plucks).min()
+    }
+
+    /// Get the gate changes due at the given instant
+    pub fn poll(&mut self, now: Instant) -> Vec<StringEvent> {
+        let mut events = vec![];
+
+        for (string, (gate, changed)) in self.gates.iter_mut() {
+            if *gate && now >= *changed + GATE_DURATION {
+                *gate = false;
+                *changed = now;
+                events.push(StringEvent::Release(*string));
+            }
+        }
+
+        let gates = &mut self.gates;
+        self.scheduled.retain(|pluck| {
+            if pluck.at > now {
+                return true;
+            }
+            // A string gate must stay released for a while to be plucked again
+            let ready = gates
+                .get(&pluck.string)
+                .map(|(gate, changed)| !gate && now >= *changed + GATE_DURATION)
+                .unwrap_or(true);
+            if ready {
+                gates.insert(pluck.string, (true, now));
+                events.push(StringEvent::Pluck(pluck.string, pluck.velocity));
+            }
+            !ready
+        });
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn detect_test() {
+        let mut engine = StrumEngine::default();
+        let start = Instant::now();
+        let ms = Duration::from_millis(1);
+        assert_eq!(None, engine.detect(1.0, true, start));
+        let strum = engine.detect(2.0, true, start + ms * 100).unwrap();
+        assert_eq!(StrumDirection::Down, strum.direction);
+        assert!((strum.velocity - 10.0 / MAX_SPEED).abs() < 0.01);
+        // Not back enough
+        assert_eq!(None, engine.detect(STRUM_ANGLE, true, start + ms * 200));
+        let strum = engine.detect(1.0, true, start + ms * 300).unwrap();
+        assert_eq!(StrumDirection::Up, strum.direction);
+        // Not ready
+        assert_eq!(None, engine.detect(2.0, false, start + ms * 400));
+    }
+
+    #[rstest]
+    #[case(StrumDirection::Down, vec![0, 1, 2])]
+    #[case(StrumDirection::Up, vec![2, 1, 0])]
+    fn schedule_test(#[case] direction: StrumDirection, #[case] expected: Vec<usize>) {
+        let mut engine = StrumEngine::default();
+        let start = Instant::now();
+        let spacing = Duration::from_millis(10);
+        let strum = Strum {
+            direction,
+            velocity: 0.5,
+        };
+        engine.schedule(strum, &[0, 1, 2], spacing, start);
+        let plucks: Vec<usize> = (0..3)
+            .flat_map(|i| engine.poll(start + spacing * i))
+            .filter_map(|e| match e {
+                StringEvent::Pluck(s, _) => Some(s),
+                StringEvent::Release(_) => None,
+            })
+            .collect();
+        assert_eq!(expected, plucks);
+    }
+
+    #[rstest]
+    fn next_deadline_test() {
+        let mut engine = StrumEngine::default();
+        let start = Instant::now();
+        assert_eq!(None, engine.next_deadline());
+        let strum = Strum {
+            direction: StrumDirection::Down,
+            velocity: 1.0,
+        };
+        let spacing = Duration::from_millis(10);
+        engine.schedule(strum, &[0, 1], spacing, start);
+        assert_eq!(Some(start), engine.next_deadline());
+        engine.poll(start);
+        assert_eq!(Some(start + spacing), engine.next_deadline());
+        engine.poll(start + spacing);
+        assert_eq!(Some(start + GATE_DURATION), engine.next_deadline());
+        engine.poll(start + spacing + GATE_DURATION);
+        assert_eq!(None, engine.next_deadline());
+    }
+
+    #[rstest]
+    fn release_test() {
+        let mut engine = StrumEngine::default();
+        let start = Instant::now();
+        let strum = Strum {
+            direction: StrumDirection::Down,
+            velocity: 1.0,
+        };
+        engine.schedule(strum, &[0], Duration::ZERO, start);
+        engine.schedule(strum, &[0], Duration::ZERO, start);
+        assert_eq!(vec![StringEvent::Pluck(0, 1.0)], engine.poll(start));
+        assert_eq!(
+            vec![StringEvent::Release(0)],
+            engine.poll(start + GATE_DURATION)
+        );
+        assert_eq!(
+            vec![StringEvent::Pluck(0, 1.0)],
+            engine.poll(start + GATE_DURATION * 2)
+        );
+    }
+}
@@ -0,0 +1,228 @@
+use std::{
+    collections::VecDeque,
+    ops::RangeInclusive,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Accepted tempo range
+pub const TEMPO_RANGE: RangeInclusive<f32> = 40.0..=240.0;
+
+/// Maximum delay between two taps of the same tap tempo sequence
+const TAP_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Number of taps averaged for the tap tempo
+const TAP_COUNT: usize = 4;
+
+/// MIDI clock pulses per quarter note
+const MIDI_PPQN: usize = 24;
+
+/// Synchronisation of a duration on the tempo
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TempoSync {
+    /// Not synchronised
+    #[default]
+    Free,
+    /// 1/2
+    Half,
+    /// 1/4
+    Quarter,
+    /// Dotted 1/8
+    DottedEighth,
+    /// 1/8
+    Eighth,
+    /// 1/4 triplet
+    QuarterTriplet,
+    /// 1/8 triplet
+    EighthTriplet,
+    /// 1/16
+    Sixteenth,
+}
+
+impl TempoSync {
+    /// Length in beats (quarter notes), or None when not synchronised
+    pub fn beats(&self) -> Option<f32> {
+        match self {
+            TempoSync::Free => None,
+            TempoSync::Half => Some(2.0),
+            TempoSync::Quarter => Some(1.0),
+            TempoSync::DottedEighth => Some(0.75),
+            TempoSync::Eighth => Some(0.5),
+            TempoSync::QuarterTriplet => Some(2.0 / 3.0),
+            TempoSync::EighthTriplet => Some(1.0 / 3.0),
+            TempoSync::Sixteenth => Some(0.25),
+        }
+    }
+
+    /// Duration at the given tempo, or None when not synchronised or when
+    /// the tempo is not positive
+    pub fn duration(&self, bpm: f32) -> Option<Duration> {
+        if bpm <= 0.0 {
+            return None;
+        }
+        self.beats()
+            .map(|beats| Duration::from_secs_f32(beats * 60.0 / bpm))
+    }
+}
+
+/// Tempo clock, following the tap tempo or an external MIDI clock
+#[derive(Debug)]
+pub struct TempoClock {
+    /// Time of the first beat
+    origin: Instant,
+    /// Last taps of the tap tempo
+    taps: VecDeque<Instant>,
+    /// Last received MIDI clock pulses
+    midi_pulses: VecDeque<Instant>,
+}
+
+impl Default for TempoClock {
+    fn default() -> Self {
+        Self::new(Instant::now())
+    }
+}
+
+impl TempoClock {
+    /// Creates a new [`TempoClock`] starting now.
+    pub fn new(now: Instant) -> Self {
+        Self {
+            origin: now,
+            taps: VecDeque::with_capacity(TAP_COUNT),
+            midi_pulses: VecDeque::with_capacity(MIDI_PPQN + 1),
+        }
+    }
+
+    /// Restart the beat count
+    pub fn restart(&mut self, now: Instant) {
+        self.origin = now;
+        self.midi_pulses.clear();
+    }
+
+    /// Register a tap of the tap tempo.
+    ///
+    /// Returns the new tempo once at least two taps are close enough.
+    pub fn tap(&mut self, now: Instant) -> Option<f32> {
+        if let Some(last) = self.taps.back() {
+            if now.duration_since(*last) > TAP_TIMEOUT {
+                self.taps.clear();
+            }
+        }
+        if self.taps.len() == TAP_COUNT {
+            self.taps.pop_front();
+        }
+        self.taps.push_back(now);
+        if self.taps.len() == 1 {
+            // Beats are aligned on the first tap
+            self.origin = now;
+        }
+
+        let first = self.taps.front()?;
+        let intervals = self.taps.len() - 1;
+        if intervals == 0 {
+            return None;
+        }
+        let beat = now.duration_since(*first).as_secs_f32() / intervals as f32;
+        Some(60.0 / beat)
+    }
+
+    /// Register a MIDI clock pulse.
+    ///
+    /// Returns the measured tempo once per beat.
+    pub fn midi_pulse(&mut self, now: Instant) -> Option<f32> {
+        if self.midi_pulses.len() > MIDI_PPQN {
+            self.midi_pulses.pop_front();
+        }
+        self.midi_pulses.push_back(now);
+        if self.midi_pulses.len() <= MIDI_PPQN {
+            return None;
+        }
+
+        let first = self.midi_pulses.front()?;
+        let beat = now.duration_since(*first).as_secs_f32();
+        if beat <= 0.0 {
+            return None;
+        }
+        let bpm = 60.0 / beat;
+        // Only report once per beat, aligned on the pulses
+        self.midi_pulses.drain(..MIDI_PPQN);
+        Some((bpm * 10.0).round() / 10.0)
+    }
+
+    /// Number of beats elapsed since the origin at the given tempo
+    pub fn beats(&self, bpm: f32, now: Instant) -> f32 {
+        now.saturating_duration_since(self.origin).as_secs_f32() * bpm / 60.0
+    }
+
+    /// Next instant aligned on the grid of the given synchronisation.
+    ///
+    /// Returns `now` when not synchronised or when the tempo is not positive.
+    pub fn next_grid(&self, sync: TempoSync, bpm: f32, now: Instant) -> Instant {
+        match sync.beats() {
+            Some(grid) if bpm > 0.0 => {
+                let beats = self.beats(bpm, now);
+                let next = (beats / grid).ceil() * grid;
+                now + Duration::from_secs_f32((next - beats) * 60.0 / bpm)
+            }
+            _ => now,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(TempoSync::Quarter, 120.0, Some(500))]
+    #[case(TempoSync::DottedEighth, 120.0, Some(375))]
+    #[case(TempoSync::EighthTriplet, 100.0, Some(200))]
+    #[case(TempoSync::Free, 120.0, None)]
+    #[case(TempoSync::Quarter, 0.0, None)]
+    #[case(TempoSync::Quarter, -60.0, None)]
+    fn duration_test(#[case] sync: TempoSync, #[case] bpm: f32, #[case] expected: Option<u64>) {
+        assert_eq!(
+            expected,
+            sync.duration(bpm)
+                .map(|d| d.as_millis().try_into().unwrap())
+        );
+    }
+
+    #[rstest]
+    fn tap_test() {
+        let start = Instant::now();
+        let mut clock = TempoClock::new(start);
+        assert_eq!(None, clock.tap(start));
+        let bpm = clock.tap(start + Duration::from_millis(500)).unwrap();
+        assert!((bpm - 120.0).abs() < 0.1);
+        let bpm = clock.tap(start + Duration::from_millis(1100)).unwrap();
+        assert!((bpm - 109.1).abs() < 0.1);
+
+        // Too late, start a new sequence
+        assert_eq!(None, clock.tap(start + Duration::from_secs(5)));
+    }
+
+    #[rstest]
+    fn midi_pulse_test() {
+        let start = Instant::now();
+        let mut clock = TempoClock::new(start);
+        let pulse = Duration::from_secs_f32(0.5 / MIDI_PPQN as f32);
+        let bpms: Vec<f32> = (0..(MIDI_PPQN as u32 * 2 + 1))
+            .filter_map(|i| clock.midi_pulse(start + pulse * i))
+            .collect();
+        assert_eq!(vec![120.0, 120.0], bpms);
+    }
+
+    #[rstest]
+    fn next_grid_test() {
+        let start = Instant::now();
+        let clock = TempoClock::new(start);
+        let now = start + Duration::from_millis(600);
+        let next = clock.next_grid(TempoSync::Quarter, 120.0, now);
+        assert!(((next - start).as_secs_f32() - 1.0).abs() < 0.001);
+        assert_eq!(now, clock.next_grid(TempoSync::Free, 120.0, now));
+        assert_eq!(now, clock.next_grid(TempoSync::Quarter, 0.0, now));
+    }
+}
@@ -1,18 +1,35 @@
-use std::{cmp::Ordering, f32::consts::PI, thread};
+use std::{cmp::Ordering, f32::consts::PI, iter, ops::RangeInclusive, thread, time::Instant};
 
 use itertools::Itertools;
 use nalgebra::Vector2;
 use staff::{midi::Octave, Interval, Pitch};
-use std::sync::mpsc::{Receiver, Sender};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
 
 use crate::{
     controls,
-    settings::{Handedness, NamedScale, Preset, Settings},
-    thread_dsp, thread_leap, thread_ui, HandMessage, {IntervalF, Volume},
+    history::History,
+    looper::{LooperCommand, LooperStatus},
+    morph::{Morph, MorphMode},
+    settings::{
+        EnvelopeSettings, GestureAction, Handedness, Limits, NamedScale, Preset, Scene, Settings,
+        SharedFile,
+    },
+    solfege::{chord_degrees, ArpPattern, Arpeggiator, EdoScale, TuningFile, EDO_DIVISIONS},
+    strum::{StringEvent, StrumEngine},
+    tempo::{TempoClock, TempoSync, TEMPO_RANGE},
+    thread_dsp, thread_leap, thread_midi, thread_saver, thread_ui,
+    vibrato::{Vibrato, VibratoGesture},
+    HandMessage, {IntervalF, Volume},
 };
 
 const HALF_PI: f32 = PI / 2.0;
 
+/// Accepted reference pitch range (Hz)
+const REFERENCE_PITCH_RANGE: RangeInclusive<f32> = 415.0..=466.0;
+
+/// Volume above which a voice opens its envelope
+const VOICE_GATE: f32 = 0.05;
+
 #[derive(Debug)]
 pub enum TrackingStatus {
     Error(String),
@@ -58,8 +75,83 @@ pub enum Msg {
     ReverbTime(f32),
     ReverbDamp(f32),
     ReverbSize(f32),
+    DistortionAmount(Volume),
+    DistortionDrive(f32),
+    EqLow(f32),
+    EqMid(f32),
+    EqHigh(f32),
+    ChorusAmount(Volume),
+    ChorusRate(f32),
+    ChorusDepth(f32),
+    PhaserAmount(Volume),
+    PhaserRate(f32),
+    PhaserFeedback(f32),
     DroneDetune(f32),
+    WaveSine(f32),
+    WaveTriangle(f32),
+    WaveSquare(f32),
+    WavePulseWidth(f32),
+    WaveSaw(f32),
+    WaveTheremin(f32),
+    VibratoRate(f32),
+    VibratoDepth(f32),
+    VibratoOnset(f32),
+    VibratoGesture(VibratoGesture),
+    GlideTime(f32),
+    LegatoClicked,
+    LeadEnvelope(EnvelopeSettings),
+    DroneEnvelope(EnvelopeSettings),
+    /// Value of a parameter saved in the presets, by DSP path
+    SetParameter(String, f32),
     GuitarDroneClicked,
+    Tempo(f32),
+    ArpPattern(ArpPattern),
+    ArpDivision(i32),
+    ArpGate(f32),
+    TapTempo,
+    EchoSync(TempoSync),
+    FistAction(GestureAction),
+    /// MIDI clock pulse, 24 per quarter note
+    MidiClock(Instant),
+    /// MIDI start or continue
+    MidiStart(Instant),
+    Looper(LooperCommand),
+    StrumSpacing(f32),
+    StrumQuantize(TempoSync),
+    LoadTuning(String),
+    /// Scala file read by the saver thread
+    TuningLoaded(TuningFile),
+    ResetTuning,
+    /// Frequency of A4 (Hz)
+    ReferencePitch(f32),
+    /// Number of divisions of the octave, 0 to go back to 12-TET
+    Edo(i32),
+    EdoDegreeClicked(i32),
+    /// Import presets and scales from a shared file
+    Import(String),
+    /// Shared file read by the saver thread
+    Imported(Box<SharedFile>),
+    ExportPreset(String),
+    ExportScale(String),
+    /// Export all the user presets and scales
+    ExportBundle(String),
+    MorphMode(MorphMode),
+    MorphDuration(f32),
+    MorphSwitch(f32),
+    NextScene,
+    PreviousScene,
+    SelectScene(i32),
+    /// Add the current preset at the end of the setlist
+    AddScene,
+    RemoveScene(i32),
+    /// Notes of the current scene
+    SceneNotes(String),
+    /// Go back to the settings before the last change
+    Undo,
+    /// Apply again the last undone change
+    Redo,
+    /// The settings file or the presets directory was modified on the disk
+    SettingsReloaded(Box<Settings>),
 }
 
 pub fn run(
@@ -69,6 +161,8 @@ pub fn run(
     dsp_tx: Sender<thread_dsp::Msg>,
     ui_tx: Sender<thread_ui::Msg>,
     leap_tx: Sender<thread_leap::Msg>,
+    midi_tx: Sender<thread_midi::Msg>,
+    saver_tx: Sender<thread_saver::Msg>,
 ) -> thread::JoinHandle<()> {
     thread::Builder::new()
         .name("conductor".to_string())
@@ -79,7 +173,10 @@ pub fn run(
                 dsp_tx,
                 ui_tx,
                 leap_tx,
+                midi_tx,
+                saver_tx,
                 play_state: PlayState::default(),
+                history: History::default(),
             };
             conductor.run(rx).unwrap();
         })
@@ -98,6 +195,12 @@ struct Conductor {
     /// Output: Update to leap thread
     pub leap_tx: Sender<thread_leap::Msg>,
 
+    /// Output: Update to MIDI thread
+    pub midi_tx: Sender<thread_midi::Msg>,
+
+    /// Output: Settings to save
+    pub saver_tx: Sender<thread_saver::Msg>,
+
     /// Application settings current state
     pub settings: Settings,
 
@@ -106,36 +209,112 @@ struct Conductor {
 
     /// Stateful playing state
     pub play_state: PlayState,
+
+    /// Previous settings states, to undo the changes
+    pub history: History<Settings>,
 }
 
 /// Stateful part of the playing interactions that are not part of the DSP
+#[derive(Default)]
 struct PlayState {
-    pub guitar_gates: [bool; 4],
+    pub guitar_gates: Vec<bool>,
     pub drone_grab_state: Option<(f32, f32)>,
     pub drone_state: f32,
+    pub arpeggiator: Arpeggiator,
+    pub tempo_clock: TempoClock,
+    /// Tempo of the external MIDI clock, overriding the preset one
+    pub clock_tempo: Option<f32>,
+    pub fist: bool,
+    pub looper: LooperStatus,
+    pub strum: StrumEngine,
+    pub vibrato: Vibrato,
+    /// Transition to a new preset
+    pub morph: Option<Morph>,
 }
 
-impl Default for PlayState {
-    fn default() -> Self {
-        Self {
-            guitar_gates: [false, false, false, false],
-            drone_grab_state: None,
-            drone_state: 0.0,
-        }
+impl PlayState {
+    /// Tempo being played, from the MIDI clock or else from the preset
+    fn tempo(&self, preset: &Preset) -> f32 {
+        self.clock_tempo.unwrap_or(preset.tempo)
     }
 }
 
 impl Conductor {
     pub fn run(&mut self, rx: Receiver<Msg>) -> anyhow::Result<()> {
-        for msg in rx.iter() {
-            let exit = self.on_conductor_message(msg)?;
-            if exit {
-                return Ok(());
+        loop {
+            // Sleep until the next scheduled event, or the next message
+            let received = match self.next_deadline() {
+                Some(deadline) => {
+                    rx.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(msg) => {
+                    let exit = self.on_conductor_message(msg)?;
+                    if exit {
+                        return Ok(());
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            self.on_tick()?;
+        }
+    }
+
+    /// Next instant at which a scheduled event is due, if any
+    fn next_deadline(&self) -> Option<Instant> {
+        let morph = self.play_state.morph.as_ref().and_then(Morph::next_update);
+        morph
+            .into_iter()
+            .chain(self.play_state.strum.next_deadline())
+            .min()
+    }
+
+    /// Play the scheduled events
+    fn on_tick(&mut self) -> anyhow::Result<()> {
+        self.on_morph()?;
+        for event in self.play_state.strum.poll(Instant::now()) {
+            match event {
+                StringEvent::Pluck(string, velocity) => {
+                    self.controls
+                        .string(string)
+                        .send_pluck(&self.dsp_tx, velocity)?;
+                }
+                StringEvent::Release(string) => {
+                    self.controls.string(string).pluck.send(&self.dsp_tx, false);
+                }
             }
         }
         Ok(())
     }
 
+    /// Apply the current morph position
+    fn on_morph(&mut self) -> anyhow::Result<()> {
+        let morph = match &mut self.play_state.morph {
+            Some(morph) => morph,
+            None => return Ok(()),
+        };
+        let preset = match morph.update(Instant::now()) {
+            Some(preset) => preset,
+            None => return Ok(()),
+        };
+        let done = morph.is_done();
+        preset.send_to_dsp(&self.controls, &self.dsp_tx)?;
+        self.settings.current_preset = preset;
+        if done {
+            // Only shown once reached, the UI rebuilds all its models from
+            // the settings
+            self.play_state.morph = None;
+            self.ui_tx
+                .send(thread_ui::Msg::Settings(self.settings.clone()))?;
+            self.saver_tx
+                .send(thread_saver::Msg::Save(self.settings.clone()))?;
+        }
+        Ok(())
+    }
+
     fn on_conductor_message(&mut self, msg: Msg) -> anyhow::Result<bool> {
         let mut settings = self.settings.clone();
 
@@ -150,6 +329,8 @@ impl Conductor {
                 self.dsp_tx.send(thread_dsp::Msg::Exit)?;
                 self.ui_tx.send(thread_ui::Msg::Exit)?;
                 self.leap_tx.send(thread_leap::Msg::Exit)?;
+                self.midi_tx.send(thread_midi::Msg::Exit)?;
+                self.saver_tx.send(thread_saver::Msg::Exit)?;
                 return Ok(true);
             }
             Msg::TrackingStatus(status) => {
@@ -159,14 +340,26 @@ impl Conductor {
                 if h.hand_type == pitch_hand_type {
                     self.on_pitch_hand(h, preset)?;
                 } else if h.hand_type == volume_hand_type {
+                    let fist = self.on_fist(&h);
+                    if let Some(morph) = &mut self.play_state.morph {
+                        morph.gesture(h.grab);
+                    }
                     self.on_volume_hand(h, preset)?;
+                    if fist {
+                        match settings.system.fist_action {
+                            GestureAction::None => {}
+                            GestureAction::TapTempo => self.tap_tempo(&mut settings.current_preset),
+                            GestureAction::Looper => self.looper(LooperCommand::Record)?,
+                            GestureAction::NextScene => self.step_scene(&mut settings, 1),
+                        }
+                    }
                 }
             }
             Msg::VisibleHands { left, right } => {
                 self.ui_tx.send(thread_ui::Msg::HasHands(left, right))?;
             }
             Msg::DroneClicked(note_index) => {
-                toggle_drone(preset, note_index);
+                toggle_drone(preset, note_index, self.controls.drone_notes.len());
             }
             Msg::RootClicked(p) => {
                 let pitch = Pitch::from_byte((p % 12) as u8);
@@ -201,11 +394,12 @@ impl Conductor {
                 preset.drone_octave = Octave::new_unchecked(o as i8);
             }
             Msg::SelectScale(id) => {
-                if let Some((scale, _)) = settings
+                let scale = settings
                     .system_and_user_scales()
                     .find(|(s, _)| s.id() == id)
-                {
-                    settings.current_preset.scale = scale.scale;
+                    .map(|(s, _)| s.scale);
+                if let Some(scale) = scale {
+                    settings.current_preset.scale = scale;
                 }
             }
             Msg::DeleteScale(id) => {
@@ -222,7 +416,7 @@ impl Conductor {
                     .find(|(p, _)| p.id() == id)
                     .map(|(p, _)| p.clone());
                 if let Some(preset) = preset {
-                    settings.current_preset = preset;
+                    self.select_preset(&mut settings, preset);
                 }
             }
             Msg::DeletePreset(id) => {
@@ -238,30 +432,315 @@ impl Conductor {
             Msg::DroneVolume(v) => preset.mix.drone = v,
             Msg::MasterVolume(v) => preset.mix.master = v,
             Msg::EchoAmount(v) => preset.fx.echo.mix = v,
-            Msg::EchoDuration(v) => preset.fx.echo.duration = v,
+            Msg::EchoDuration(v) => {
+                preset.fx.echo.duration = v;
+                preset.fx.echo.sync = TempoSync::Free;
+            }
             Msg::EchoFeedback(v) => preset.fx.echo.feedback = v,
             Msg::ReverbAmount(v) => preset.fx.reverb.mix = v,
             Msg::ReverbTime(v) => preset.fx.reverb.time = v,
             Msg::ReverbDamp(v) => preset.fx.reverb.damp = v,
             Msg::ReverbSize(v) => preset.fx.reverb.size = v,
+            Msg::DistortionAmount(v) => preset.fx.distortion.mix = v,
+            Msg::DistortionDrive(v) => preset.fx.distortion.drive = v,
+            Msg::EqLow(v) => preset.fx.eq.low = v,
+            Msg::EqMid(v) => preset.fx.eq.mid = v,
+            Msg::EqHigh(v) => preset.fx.eq.high = v,
+            Msg::ChorusAmount(v) => preset.fx.chorus.mix = v,
+            Msg::ChorusRate(v) => preset.fx.chorus.rate = v,
+            Msg::ChorusDepth(v) => preset.fx.chorus.depth = v,
+            Msg::PhaserAmount(v) => preset.fx.phaser.mix = v,
+            Msg::PhaserRate(v) => preset.fx.phaser.rate = v,
+            Msg::PhaserFeedback(v) => preset.fx.phaser.feedback = v,
             Msg::DroneDetune(v) => preset.drone.detune = v,
+            Msg::WaveSine(v) => preset.wave.sine = v,
+            Msg::WaveTriangle(v) => preset.wave.triangle = v,
+            Msg::WaveSquare(v) => preset.wave.square = v,
+            Msg::WavePulseWidth(v) => preset.wave.pulse_width = v,
+            Msg::WaveSaw(v) => preset.wave.saw = v,
+            Msg::WaveTheremin(v) => preset.wave.theremin = v,
+            Msg::VibratoRate(v) => preset.vibrato.rate = v,
+            Msg::VibratoDepth(v) => preset.vibrato.depth = v,
+            Msg::VibratoOnset(v) => preset.vibrato.onset = v,
+            Msg::VibratoGesture(g) => preset.vibrato.gesture = g,
+            Msg::GlideTime(v) => preset.glide.time = v,
+            Msg::LegatoClicked => preset.glide.legato = !preset.glide.legato,
+            Msg::LeadEnvelope(envelope) => preset.lead_envelope = envelope,
+            Msg::DroneEnvelope(envelope) => preset.drone_envelope = envelope,
+            Msg::SetParameter(path, v) => {
+                match self.controls.parameters.iter().find(|c| c.path == path) {
+                    Some(control) => {
+                        let range = &control.input.range;
+                        preset
+                            .parameters
+                            .insert(path, v.clamp(*range.start(), *range.end()));
+                    }
+                    None => log::warn!("Unknown parameter {}", path),
+                }
+            }
             Msg::GuitarDroneClicked => preset.drone.pluck_drone = !preset.drone.pluck_drone,
+            Msg::Tempo(t) => {
+                self.play_state.clock_tempo = None;
+                preset.tempo = t.clamp(*TEMPO_RANGE.start(), *TEMPO_RANGE.end());
+            }
+            Msg::ArpPattern(p) => preset.arpeggiator.pattern = p,
+            Msg::ArpDivision(d) => preset.arpeggiator.division = d.clamp(1, 8) as u8,
+            Msg::ArpGate(g) => preset.arpeggiator.gate = g,
+            Msg::TapTempo => self.tap_tempo(preset),
+            Msg::EchoSync(s) => preset.fx.echo.sync = s,
+            Msg::FistAction(a) => settings.system.fist_action = a,
+            Msg::MidiClock(now) => {
+                // Followed without changing the preset, not to save or
+                // record it at every beat
+                if let Some(bpm) = self.play_state.tempo_clock.midi_pulse(now) {
+                    let bpm = bpm.clamp(*TEMPO_RANGE.start(), *TEMPO_RANGE.end());
+                    if self.play_state.clock_tempo != Some(bpm) {
+                        self.play_state.clock_tempo = Some(bpm);
+                        self.send_clock_tempo(preset)?;
+                    }
+                }
+            }
+            Msg::MidiStart(now) => {
+                self.play_state.tempo_clock.restart(now);
+                self.play_state.arpeggiator.reset();
+            }
+            Msg::Looper(command) => self.looper(command)?,
+            Msg::StrumSpacing(s) => preset.strum.spacing = s,
+            Msg::StrumQuantize(q) => preset.strum.quantize = q,
+            Msg::LoadTuning(path) => self
+                .saver_tx
+                .send(thread_saver::Msg::LoadTuning(clean_path(&path).into()))?,
+            Msg::TuningLoaded(file) => {
+                preset.tuning = Some(file.apply(preset.tuning.take()));
+                preset.edo = None;
+            }
+            Msg::ResetTuning => {
+                preset.tuning = None;
+                preset.edo = None;
+            }
+            Msg::ReferencePitch(p) => {
+                preset.reference_pitch =
+                    p.clamp(*REFERENCE_PITCH_RANGE.start(), *REFERENCE_PITCH_RANGE.end())
+            }
+            Msg::Edo(divisions) => {
+                preset.edo = u8::try_from(divisions)
+                    .ok()
+                    .filter(|d| EDO_DIVISIONS.contains(d))
+                    .map(|d| EdoScale::from_scale(preset.scale, d));
+                preset.tuning = None;
+            }
+            Msg::EdoDegreeClicked(degree) => {
+                if let (Some(edo), Ok(degree)) = (&mut preset.edo, u8::try_from(degree)) {
+                    edo.toggle(degree);
+                }
+            }
+            Msg::Import(path) => self
+                .saver_tx
+                .send(thread_saver::Msg::Import(clean_path(&path).into()))?,
+            Msg::Imported(file) => {
+                let count = settings.import(*file);
+                log::info!("Imported {} presets and scales", count);
+                for issue in settings.validate(&Limits::from(&self.controls)) {
+                    log::warn!("Corrected imported setting: {}", issue);
+                }
+            }
+            Msg::ExportPreset(path) => {
+                self.export(&path, SharedFile::Preset(settings.current_preset.clone()))?
+            }
+            Msg::ExportScale(path) => {
+                let current = &settings.current_preset;
+                let scale = settings
+                    .system_and_user_scales()
+                    .map(|(s, _)| s)
+                    .find(|s| s.scale == current.scale)
+                    .unwrap_or_else(|| NamedScale::new(current.name.clone(), current.scale));
+                self.export(&path, SharedFile::Scale(scale))?
+            }
+            Msg::ExportBundle(path) => self.export(&path, settings.export_bundle())?,
+            Msg::MorphMode(m) => settings.morph.mode = m,
+            Msg::MorphDuration(d) => settings.morph.duration = d,
+            Msg::MorphSwitch(s) => settings.morph.switch_at = s,
+            Msg::NextScene => self.step_scene(&mut settings, 1),
+            Msg::PreviousScene => self.step_scene(&mut settings, -1),
+            Msg::SelectScene(index) => {
+                if let Ok(index) = usize::try_from(index) {
+                    self.go_to_scene(&mut settings, index);
+                }
+            }
+            Msg::AddScene => settings.setlist.scenes.push(Scene {
+                preset: settings.current_preset.name.clone(),
+                notes: String::new(),
+            }),
+            Msg::RemoveScene(index) => {
+                if let Ok(index) = usize::try_from(index) {
+                    settings.setlist.remove(index);
+                }
+            }
+            Msg::SceneNotes(notes) => {
+                let current = settings.setlist.current;
+                if let Some(scene) = settings.setlist.scenes.get_mut(current) {
+                    scene.notes = notes;
+                }
+            }
+            Msg::SettingsReloaded(reloaded) => {
+                let mut reloaded = *reloaded;
+                for issue in reloaded.validate(&Limits::from(&self.controls)) {
+                    log::warn!("Corrected reloaded setting: {}", issue);
+                }
+                // Play the edits of the preset file being played
+                let edited = reloaded
+                    .directory_presets
+                    .iter()
+                    .find(|p| p.name == settings.current_preset.name)
+                    .filter(|p| !settings.directory_presets.contains(p))
+                    .cloned();
+                if let Some(preset) = edited {
+                    reloaded.current_preset = preset;
+                }
+                log::info!("Reloaded the settings from the disk");
+                // Already on the disk, and not an edit to undo
+                if reloaded != self.settings {
+                    self.play_state.morph = None;
+                    self.play_settings(reloaded)?;
+                }
+                return Ok(false);
+            }
+            Msg::Undo => {
+                if let Some(previous) = self.history.undo(self.settings.clone()) {
+                    self.play_state.morph = None;
+                    self.apply_settings(previous)?;
+                }
+                return Ok(false);
+            }
+            Msg::Redo => {
+                if let Some(next) = self.history.redo(self.settings.clone()) {
+                    self.play_state.morph = None;
+                    self.apply_settings(next)?;
+                }
+                return Ok(false);
+            }
         }
 
         if settings != self.settings {
-            tracing::debug!("Settings were updated");
-            self.ui_tx
-                .send(thread_ui::Msg::Settings(settings.clone()))?;
-            settings
-                .current_preset
-                .send_to_dsp(&self.controls, &self.dsp_tx)?;
-            self.settings = settings;
-            self.settings.save()?;
+            // The morph would revert the edits of the current preset
+            if settings.current_preset != self.settings.current_preset {
+                self.play_state.morph = None;
+            }
+            self.history.record(self.settings.clone(), Instant::now());
+            self.apply_settings(settings)?;
         }
 
         Ok(false)
     }
 
+    /// Replace the current settings, propagating them to the other threads
+    fn apply_settings(&mut self, settings: Settings) -> anyhow::Result<()> {
+        self.play_settings(settings)?;
+        self.saver_tx
+            .send(thread_saver::Msg::Save(self.settings.clone()))?;
+        Ok(())
+    }
+
+    /// Replace the current settings without saving them
+    fn play_settings(&mut self, settings: Settings) -> anyhow::Result<()> {
+        tracing::debug!("Settings were updated");
+        self.ui_tx
+            .send(thread_ui::Msg::Settings(settings.clone()))?;
+        settings
+            .current_preset
+            .send_to_dsp(&self.controls, &self.dsp_tx)?;
+        if self.play_state.clock_tempo.is_some() {
+            self.send_clock_tempo(&settings.current_preset)?;
+        }
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// Play and show the tempo of the MIDI clock instead of the preset one
+    fn send_clock_tempo(&self, preset: &Preset) -> anyhow::Result<()> {
+        let tempo = self.play_state.tempo(preset);
+        preset
+            .fx
+            .echo
+            .send_to_dsp(&self.controls, &self.dsp_tx, tempo)?;
+        self.ui_tx.send(thread_ui::Msg::Tempo(tempo))?;
+        Ok(())
+    }
+
+    /// Detect the closing of the hand, with some hysteresis
+    fn on_fist(&mut self, h: &HandMessage) -> bool {
+        let was_fist = self.play_state.fist;
+        if h.grab >= 0.95 {
+            self.play_state.fist = true;
+        } else if h.grab < 0.5 {
+            self.play_state.fist = false;
+        }
+        self.play_state.fist && !was_fist
+    }
+
+    /// Switch to a preset, morphing to it when enabled
+    fn select_preset(&mut self, settings: &mut Settings, preset: Preset) {
+        if settings.morph.mode == MorphMode::Off {
+            settings.current_preset = preset;
+        } else {
+            // The whole morph is undone at once
+            self.history.record(settings.clone(), Instant::now());
+            self.play_state.morph = Some(Morph::new(
+                settings.current_preset.clone(),
+                preset,
+                settings.morph.clone(),
+                Instant::now(),
+            ));
+        }
+    }
+
+    /// Play a scene of the setlist
+    fn go_to_scene(&mut self, settings: &mut Settings, index: usize) {
+        let name = match settings.setlist.go_to(index) {
+            Some(scene) => scene.preset.clone(),
+            None => return,
+        };
+        let preset = settings
+            .system_and_user_presets()
+            .find(|(p, _)| p.name == name)
+            .map(|(p, _)| p.clone());
+        match preset {
+            Some(preset) => self.select_preset(settings, preset),
+            None => log::warn!("Unknown preset {} in the setlist", name),
+        }
+    }
+
+    /// Write a shared file from the saver thread
+    fn export(&self, path: &str, file: SharedFile) -> anyhow::Result<()> {
+        self.saver_tx.send(thread_saver::Msg::Export(
+            clean_path(path).into(),
+            Box::new(file),
+        ))?;
+        Ok(())
+    }
+
+    /// Move in the setlist from the current scene
+    fn step_scene(&mut self, settings: &mut Settings, offset: isize) {
+        let index = settings.setlist.current.saturating_add_signed(offset);
+        self.go_to_scene(settings, index);
+    }
+
+    fn tap_tempo(&mut self, preset: &mut Preset) {
+        if let Some(bpm) = self.play_state.tempo_clock.tap(Instant::now()) {
+            self.play_state.clock_tempo = None;
+            preset.tempo = bpm.clamp(*TEMPO_RANGE.start(), *TEMPO_RANGE.end()).round();
+            tracing::debug!("Tap tempo: {}", preset.tempo);
+        }
+    }
+
+    fn looper(&mut self, command: LooperCommand) -> anyhow::Result<()> {
+        self.play_state.looper.apply(command);
+        self.dsp_tx.send(thread_dsp::Msg::Looper(command))?;
+        self.ui_tx
+            .send(thread_ui::Msg::Looper(self.play_state.looper))?;
+        Ok(())
+    }
+
     fn on_pitch_hand(&mut self, h: HandMessage, preset: &Preset) -> anyhow::Result<()> {
         let dsp_tx = &mut self.dsp_tx;
         let ui_tx = &mut self.ui_tx;
@@ -278,14 +757,39 @@ impl Conductor {
         let pitch_distance_semitones = IntervalF(pitch_coord_semitones.norm());
         let raw_note = (*note_range.end() - pitch_distance_semitones)
             .clamp(*note_range.start(), *note_range.end());
-        let note_number_height =
-            controls::convert_range(position_from_body.y, &(350.0..=500.0), &(1.0..=4.0));
-        let lead_volumes =
-            [0.0, 1.0, 2.0, 3.0].map(|v| (note_number_height.clamp(1.0, 4.0) - v).clamp(0.0, 1.0));
-        self.play_state.guitar_gates = lead_volumes.map(|v| v > 0.0);
+        let voices = self.controls.lead.len();
+        let note_number_height = controls::convert_range(
+            position_from_body.y,
+            &(350.0..=500.0),
+            &(1.0..=voices as f32),
+        );
+        let lead_volumes = (0..voices)
+            .map(|v| (note_number_height.clamp(1.0, voices as f32) - v as f32).clamp(0.0, 1.0))
+            .collect_vec();
+        self.play_state.guitar_gates = lead_volumes.iter().map(|v| *v > 0.0).collect();
         let autotune = controls::convert_range(h.pinch, &(0.0..=1.0), &(0.0..=5.0)) as usize;
         let note = restricted_scale_window.autotune(raw_note, autotune);
-        let chord = full_scale_window.autochord(note, &[0, 2, 4, 7]);
+        let chord = full_scale_window.autochord(note, &chord_degrees(voices));
+        let lead_volumes = if preset.arpeggiator.pattern == ArpPattern::Off {
+            self.play_state.arpeggiator.reset();
+            lead_volumes
+        } else {
+            // The hand height chooses the density of the pattern instead of the chord size
+            let arp_notes = chord.iter().map(|c| c.unwrap_or_default()).collect_vec();
+            // The steps follow the beats of the tempo clock
+            let tempo = self.play_state.tempo(preset);
+            let beats = self.play_state.tempo_clock.beats(tempo, Instant::now());
+            let voice = self.play_state.arpeggiator.voice(
+                preset.arpeggiator.pattern,
+                &arp_notes,
+                note_number_height.round() as usize,
+                preset.arpeggiator.steps(beats),
+                preset.arpeggiator.gate,
+            );
+            (0..voices)
+                .map(|v| if Some(v) == voice { 1.0 } else { 0.0 })
+                .collect_vec()
+        };
         let lead_offset = preset.lead_interval_f();
         let pluck_offset = preset.pluck_interval_f();
         let velocity_from_body = h.velocity_from_body();
@@ -297,8 +801,9 @@ impl Conductor {
             .controls
             .drone_trumpet
             .get_scaled(velocity_from_body.y.abs(), &(0.0..=250.0));
-        for (control, value) in self.controls.lead.iter().zip(lead_volumes) {
-            control.volume.send(dsp_tx, value)?;
+        for (control, value) in self.controls.lead.iter().zip(&lead_volumes) {
+            control.volume.send(dsp_tx, *value)?;
+            control.gate.send(dsp_tx, *value > VOICE_GATE);
         }
         for (i, note) in chord.iter().enumerate() {
             if let Some(note) = note {
@@ -310,14 +815,19 @@ impl Conductor {
             dsp_tx,
             &(preset.root_note_f() + pluck_offset + IntervalF(12.0)),
         )?;
+        self.play_state
+            .vibrato
+            .pitch_hand(velocity_from_body.x + velocity_from_body.z);
+        let vibrato_depth =
+            preset.vibrato.depth * self.play_state.vibrato.amount(preset.vibrato.gesture);
         self.controls.pitch_bend.send(dsp_tx, pitch_bend)?;
         self.controls.drone_trumpet.send(dsp_tx, trumpet)?;
+        self.controls.vibrato_depth.send(dsp_tx, vibrato_depth)?;
         let lead_chord = chord
             .into_iter()
             .map(|c| c.unwrap_or_default())
             .zip(lead_volumes.into_iter().map(Volume))
             .collect_vec();
-        let lead_chord = [lead_chord[0], lead_chord[1], lead_chord[2], lead_chord[3]];
         if h.grab >= 1.0 {
             if let Some(drone_volume_angle) = h.rotation_from_body() {
                 let (init_drone_volume, init_drone_volume_angle) = *self
@@ -325,24 +835,27 @@ impl Conductor {
                     .drone_grab_state
                     .get_or_insert((self.play_state.drone_state, drone_volume_angle));
                 let offset = drone_volume_angle - init_drone_volume_angle;
-                self.play_state.drone_state = (init_drone_volume + offset).clamp(0.0, 5.0);
-                let drone_volumes = [0.0, 1.0, 2.0, 3.0]
-                    .map(|v| (self.play_state.drone_state.clamp(0.0, 4.0) - v).clamp(0.0, 1.0));
+                let drones = self.controls.drone_notes.len() as f32;
+                self.play_state.drone_state = (init_drone_volume + offset).clamp(0.0, drones + 1.0);
+                let drone_state = self.play_state.drone_state.clamp(0.0, drones);
                 let drone_interval = preset.drone_interval();
-                for ((control, drone), volume) in self
+                for (i, (control, drone)) in self
                     .controls
                     .drone_notes
                     .iter()
-                    .zip(preset.drone_notes())
-                    .zip(drone_volumes)
+                    .zip(preset.drone_notes().into_iter().chain(iter::repeat(None)))
+                    .enumerate()
                 {
+                    let volume = (drone_state - i as f32).clamp(0.0, 1.0);
                     if let Some(drone) = drone {
                         control
                             .note
                             .send(dsp_tx, ((drone + drone_interval).into_byte()) as f32)?;
                         control.volume.send(dsp_tx, volume)?;
+                        control.gate.send(dsp_tx, volume > VOICE_GATE);
                     } else {
                         control.volume.send(dsp_tx, 0.0)?;
+                        control.gate.send(dsp_tx, false);
                     }
                 }
             }
@@ -370,17 +883,28 @@ impl Conductor {
 
         let strum_ready = h.pinch > 0.9;
         if let Some(rotation) = h.rotation_from_body() {
-            if strum_ready {
-                for (i, string) in &mut self.controls.strum.iter().enumerate() {
-                    string.pluck.send(
-                        dsp_tx,
-                        rotation > HALF_PI + (i as f32) * 0.2 && self.play_state.guitar_gates[i],
-                    );
+            let now = Instant::now();
+            if let Some(strum) = self.play_state.strum.detect(rotation, strum_ready, now) {
+                let mut strings = self
+                    .play_state
+                    .guitar_gates
+                    .iter()
+                    .positions(|gate| *gate)
+                    .collect_vec();
+                if preset.drone.pluck_drone {
+                    strings.push(self.controls.strum.len());
                 }
-                self.controls
-                    .strum_drone
-                    .pluck
-                    .send(dsp_tx, preset.drone.pluck_drone && rotation > HALF_PI + 0.3);
+                let tempo = self.play_state.tempo(preset);
+                let start =
+                    self.play_state
+                        .tempo_clock
+                        .next_grid(preset.strum.quantize, tempo, now);
+                self.play_state.strum.schedule(
+                    strum,
+                    &strings,
+                    preset.strum.spacing_duration(),
+                    start,
+                );
             }
 
             let pluck_mute = self
@@ -389,6 +913,9 @@ impl Conductor {
                 .get_scaled(rotation, &(0.0..=(HALF_PI - 0.2)));
             self.controls.pluck_mute.send(dsp_tx, pluck_mute)?;
         }
+        // The depth is sent with the pitch hand updates
+        self.play_state.vibrato.volume_hand(h.tilt());
+
         let position_from_body = h.position_from_body();
         let cutoff_note_norm =
             controls::convert_range(position_from_body.x, &(50.0..=200.0), &(-1.0..=1.0))
@@ -421,6 +948,11 @@ impl Conductor {
     }
 }
 
+/// Path typed or pasted by the user, without the surrounding quotes
+fn clean_path(path: &str) -> &str {
+    path.trim().trim_matches('"')
+}
+
 fn toggle_scale_note(preset: &mut Preset, note_index: i32) {
     let root_index = preset.root_note().into_byte() as i32;
     let interval = note_index - root_index;
@@ -436,7 +968,7 @@ fn toggle_scale_note(preset: &mut Preset, note_index: i32) {
     }
 }
 
-fn toggle_drone(preset: &mut Preset, note_index: i32) {
+fn toggle_drone(preset: &mut Preset, note_index: i32, max_drones: usize) {
     let root_index = preset.root_note().into_byte() as i32;
     let interval = note_index - root_index;
     if (0..=(12 * 3)).contains(&interval) {
@@ -449,6 +981,8 @@ fn toggle_drone(preset: &mut Preset, note_index: i32) {
             *existing_drone = None;
         } else if let Some(empty_slot) = drone_intervals.iter_mut().find(|n| n.is_none()) {
             *empty_slot = Some(interval);
+        } else if drone_intervals.len() < max_drones {
+            drone_intervals.push(Some(interval));
         }
 
         drone_intervals.sort_unstable_by(|a, b| match (a, b) {
@@ -1,13 +1,19 @@
-use std::{slice, thread};
+use std::{slice, sync::mpsc, thread};
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use faust_state::{DspHandle, StateHandle};
 use faust_types::FaustDsp;
 use std::sync::mpsc::Receiver;
 
+use crate::looper::{Looper, LooperAllocator, LooperCommand};
+
+/// Maximum loop duration in seconds
+const MAX_LOOP_SECONDS: usize = 60;
+
 pub enum Msg {
     Exit,
     ParameterUpdate(ParameterUpdate),
+    Looper(LooperCommand),
 }
 
 /// Parameter update message
@@ -66,6 +72,11 @@ where
     // Init output buffers
     let mut inputs: Vec<Vec<f32>> = vec![vec![0_f32; buffer_size]; num_inputs];
     let mut outputs: Vec<Vec<f32>> = vec![vec![0_f32; buffer_size]; num_outputs];
+    // Init the looper, controlled from the audio callback. Its buffers are
+    // allocated by the controller when recording
+    let mut looper = Looper::default();
+    let mut looper_allocator = LooperAllocator::new(sample_rate as usize * MAX_LOOP_SECONDS);
+    let (looper_tx, looper_rx) = mpsc::channel();
 
     thread::Builder::new()
         .name("dsp_controller".to_string())
@@ -106,6 +117,14 @@ where
                             &buffer_input[..],
                             &mut buffer_output[..],
                         );
+                        // Record and play the loops on the master output
+                        for (command, buffer) in looper_rx.try_iter() {
+                            if let Some(buffer) = buffer {
+                                looper.add_buffer(buffer);
+                            }
+                            looper.command(command);
+                        }
+                        looper.process(&mut outputs[0][..len]);
                         // Send to audio buffer
                         for (out, dsp_sample) in data.iter_mut().zip(&outputs[0]) {
                             *out = *dsp_sample;
@@ -125,6 +144,9 @@ where
                         Msg::ParameterUpdate(parameter) => {
                             state.set_param(parameter.idx, parameter.value)
                         }
+                        Msg::Looper(command) => looper_tx
+                            .send((command, looper_allocator.buffer_for(command)))
+                            .expect("Failed to send the looper command"),
                     }
                 }
                 state.send();
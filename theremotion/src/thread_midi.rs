@@ -0,0 +1,129 @@
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread;
+
+use crate::thread_conductor;
+
+pub enum Msg {
+    Exit,
+}
+
+/// Start the MIDI input thread
+pub fn run(tx: Sender<thread_conductor::Msg>, rx: Receiver<Msg>) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("midi".to_string())
+        .spawn(move || {
+            // The connections are kept open until exit
+            #[cfg(feature = "midi")]
+            let _connections = midi::connect_all(&tx);
+
+            if let Ok(Msg::Exit) = rx.recv() {
+                log::debug!("MIDI thread exiting");
+            }
+        })
+        .expect("Failed to spawn the MIDI thread")
+}
+
+#[cfg(feature = "midi")]
+mod midi {
+    use midir::{MidiInput, MidiInputConnection};
+    use std::{sync::mpsc::Sender, time::Instant};
+
+    use crate::{looper::LooperCommand, thread_conductor::Msg};
+
+    /// Name of the MIDI client
+    const CLIENT_NAME: &str = "Theremotion";
+
+    /// Listen to all the available MIDI inputs
+    pub fn connect_all(tx: &Sender<Msg>) -> Vec<MidiInputConnection<()>> {
+        let port_count = match MidiInput::new(CLIENT_NAME) {
+            Ok(input) => input.port_count(),
+            Err(e) => {
+                log::warn!("MIDI input is not available: {}", e);
+                return vec![];
+            }
+        };
+
+        (0..port_count)
+            .filter_map(|i| {
+                // Each connection consumes its own client
+                let input = MidiInput::new(CLIENT_NAME).ok()?;
+                let port = input.ports().get(i)?.clone();
+                let name = input.port_name(&port).unwrap_or_default();
+                let tx = tx.clone();
+                let connection = input.connect(
+                    &port,
+                    "theremotion-in",
+                    move |_, message, _| {
+                        if let Some(msg) = parse(message) {
+                            // The conductor may already be gone when exiting
+                            let _ = tx.send(msg);
+                        }
+                    },
+                    (),
+                );
+                match connection {
+                    Ok(connection) => {
+                        log::info!("Listening to MIDI input {}", name);
+                        Some(connection)
+                    }
+                    Err(e) => {
+                        log::warn!("Failed to connect to MIDI input {}: {}", name, e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Translate a MIDI message for the conductor.
+    ///
+    /// The looper is controlled by the general purpose buttons 5 to 8
+    /// (CC 80 to 83) on any channel. The setlist follows the buttons 9 and 10
+    /// (CC 84 and 85) and the program changes.
+    fn parse(message: &[u8]) -> Option<Msg> {
+        match message {
+            [0xF8, ..] => Some(Msg::MidiClock(Instant::now())),
+            [0xFA | 0xFB, ..] => Some(Msg::MidiStart(Instant::now())),
+            [status, cc, value] if status & 0xF0 == 0xB0 && *value >= 64 => match cc {
+                80 => Some(Msg::Looper(LooperCommand::Record)),
+                81 => Some(Msg::Looper(LooperCommand::PlayStop)),
+                82 => Some(Msg::Looper(LooperCommand::Undo)),
+                83 => Some(Msg::Looper(LooperCommand::Clear)),
+                84 => Some(Msg::NextScene),
+                85 => Some(Msg::PreviousScene),
+                _ => None,
+            },
+            [status, program] if status & 0xF0 == 0xC0 => Some(Msg::SelectScene(*program as i32)),
+            _ => None,
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use rstest::rstest;
+
+        use super::*;
+
+        #[rstest]
+        #[case(&[0xF8], |m: &Option<Msg>| matches!(m, Some(Msg::MidiClock(_))))]
+        #[case(&[0xFA], |m: &Option<Msg>| matches!(m, Some(Msg::MidiStart(_))))]
+        #[case(&[0xFB], |m: &Option<Msg>| matches!(m, Some(Msg::MidiStart(_))))]
+        #[case(&[0xFC], |m: &Option<Msg>| m.is_none())]
+        #[case(&[0xB0, 80, 127], |m: &Option<Msg>| matches!(m, Some(Msg::Looper(LooperCommand::Record))))]
+        #[case(&[0xB3, 81, 64], |m: &Option<Msg>| matches!(m, Some(Msg::Looper(LooperCommand::PlayStop))))]
+        #[case(&[0xB0, 82, 100], |m: &Option<Msg>| matches!(m, Some(Msg::Looper(LooperCommand::Undo))))]
+        #[case(&[0xBF, 83, 127], |m: &Option<Msg>| matches!(m, Some(Msg::Looper(LooperCommand::Clear))))]
+        #[case(&[0xB0, 84, 127], |m: &Option<Msg>| matches!(m, Some(Msg::NextScene)))]
+        #[case(&[0xB0, 85, 127], |m: &Option<Msg>| matches!(m, Some(Msg::PreviousScene)))]
+        #[case(&[0xB0, 80, 63], |m: &Option<Msg>| m.is_none())]
+        #[case(&[0xB0, 84, 0], |m: &Option<Msg>| m.is_none())]
+        #[case(&[0xB0, 86, 127], |m: &Option<Msg>| m.is_none())]
+        #[case(&[0x90, 80, 127], |m: &Option<Msg>| m.is_none())]
+        #[case(&[0xC0, 3], |m: &Option<Msg>| matches!(m, Some(Msg::SelectScene(3))))]
+        #[case(&[0xC9, 0], |m: &Option<Msg>| matches!(m, Some(Msg::SelectScene(0))))]
+        #[case(&[], |m: &Option<Msg>| m.is_none())]
+        fn parse_test(#[case] message: &[u8], #[case] expected: fn(&Option<Msg>) -> bool) {
+            assert!(expected(&parse(message)), "{:?}", message);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{
+    settings::{shared_files, Settings, SharedFile},
+    solfege::TuningFile,
+    thread_conductor, thread_ui,
+};
+
+/// Time without changes before the settings are written
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// Longest time a change can wait before being written
+const SAVE_MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// Time between two checks of the files for external changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub enum Msg {
+    /// Settings to save once they stop changing
+    Save(Settings),
+    /// Write a shared file, without replacing an existing one
+    Export(PathBuf, Box<SharedFile>),
+    /// Read a shared file and send it to the conductor
+    Import(PathBuf),
+    /// Read a Scala file and send it to the conductor
+    LoadTuning(PathBuf),
+    /// Write the pending settings and stop
+    Exit,
+}
+
+/// Modification state of the watched files
+type Snapshot = Vec<(PathBuf, Option<SystemTime>, Option<u64>)>;
+
+/// Files written by the user, watched for external changes
+struct Watched {
+    path: PathBuf,
+    presets_directory: Option<PathBuf>,
+    snapshot: Snapshot,
+}
+
+impl Watched {
+    fn new(path: PathBuf, presets_directory: Option<PathBuf>) -> Self {
+        let mut watched = Self {
+            path,
+            presets_directory,
+            snapshot: vec![],
+        };
+        watched.snapshot = watched.take_snapshot();
+        watched
+    }
+
+    fn take_snapshot(&self) -> Snapshot {
+        let presets = self
+            .presets_directory
+            .as_deref()
+            .map(shared_files)
+            .unwrap_or_default();
+        std::iter::once(self.path.clone())
+            .chain(presets)
+            .map(|path| {
+                let metadata = std::fs::metadata(&path).ok();
+                let modified = metadata.as_ref().and_then(|m| m.modified().ok());
+                let len = metadata.map(|m| m.len());
+                (path, modified, len)
+            })
+            .collect()
+    }
+
+    /// Forget the changes made by the application itself
+    fn saved(&mut self, settings: &Settings) {
+        self.presets_directory = settings.system.presets_directory.clone();
+        self.snapshot = self.take_snapshot();
+    }
+
+    /// The files changed since the last check
+    fn changed(&mut self) -> bool {
+        let snapshot = self.take_snapshot();
+        if snapshot == self.snapshot {
+            return false;
+        }
+        self.snapshot = snapshot;
+        true
+    }
+}
+
+/// Start the thread writing the settings to the disk, and reloading them
+/// when they are edited outside of the application
+pub fn run(
+    rx: Receiver<Msg>,
+    ui_tx: Sender<thread_ui::Msg>,
+    co_tx: Sender<thread_conductor::Msg>,
+    path: PathBuf,
+    presets_directory: Option<PathBuf>,
+) -> thread::JoinHandle<()> {
+    thread::Builder::new()
+        .name("saver".to_string())
+        .spawn(move || {
+            let mut watched = Watched::new(path.clone(), presets_directory);
+            // Settings waiting to be saved, and since when
+            let mut pending: Option<(Settings, Instant)> = None;
+            loop {
+                let msg = match &pending {
+                    Some(_) => rx.recv_timeout(SAVE_DELAY),
+                    None => rx.recv_timeout(WATCH_INTERVAL),
+                };
+                match msg {
+                    Ok(Msg::Save(settings)) => {
+                        let since = pending.map(|(_, since)| since).unwrap_or_else(Instant::now);
+                        if since.elapsed() >= SAVE_MAX_DELAY {
+                            save(&settings, &path, &ui_tx);
+                            watched.saved(&settings);
+                            pending = None;
+                        } else {
+                            pending = Some((settings, since));
+                        }
+                    }
+                    Ok(Msg::Export(file_path, file)) => export(&file, &file_path, &ui_tx),
+                    Ok(Msg::Import(file_path)) => import(&file_path, &ui_tx, &co_tx),
+                    Ok(Msg::LoadTuning(file_path)) => load_tuning(&file_path, &ui_tx, &co_tx),
+                    Err(RecvTimeoutError::Timeout) => match pending.take() {
+                        Some((settings, _)) => {
+                            save(&settings, &path, &ui_tx);
+                            watched.saved(&settings);
+                        }
+                        None => {
+                            if watched.changed() {
+                                reload(&mut watched, &ui_tx, &co_tx);
+                            }
+                        }
+                    },
+                    Ok(Msg::Exit) | Err(RecvTimeoutError::Disconnected) => {
+                        if let Some((settings, _)) = pending.take() {
+                            save(&settings, &path, &ui_tx);
+                        }
+                        log::debug!("Saver thread exiting");
+                        return;
+                    }
+                }
+            }
+        })
+        .expect("Failed to spawn the saver thread")
+}
+
+/// Read the settings and presets edited outside of the application
+fn reload(
+    watched: &mut Watched,
+    ui_tx: &Sender<thread_ui::Msg>,
+    co_tx: &Sender<thread_conductor::Msg>,
+) {
+    let mut settings = match Settings::read_file(&watched.path) {
+        Ok(settings) => settings,
+        Err(e) => {
+            // Probably saved halfway by an editor, wait for the next change
+            log::error!("Failed to reload the settings: {:#}", e);
+            ui_tx
+                .send(thread_ui::Msg::StorageError(format!(
+                    "Failed to reload the settings: {e}"
+                )))
+                .ok();
+            return;
+        }
+    };
+    let errors = settings.load_directory_presets();
+    for error in &errors {
+        log::error!("Failed to load the preset {}", error);
+    }
+    let message = if errors.is_empty() {
+        String::new()
+    } else {
+        format!("Failed to load the preset {}", errors.join("\n"))
+    };
+    ui_tx.send(thread_ui::Msg::StorageError(message)).ok();
+
+    // The presets directory may have changed too
+    watched.saved(&settings);
+    co_tx
+        .send(thread_conductor::Msg::SettingsReloaded(Box::new(settings)))
+        .ok();
+}
+
+/// Write a shared file, reporting the failures to the user
+fn export(file: &SharedFile, path: &Path, ui_tx: &Sender<thread_ui::Msg>) {
+    match file.save(path) {
+        Ok(()) => log::info!("Exported to {}", path.display()),
+        Err(e) => {
+            log::error!("Failed to export {}: {:#}", path.display(), e);
+            ui_tx
+                .send(thread_ui::Msg::StorageError(format!(
+                    "Failed to export {}: {e}",
+                    path.display()
+                )))
+                .ok();
+        }
+    }
+}
+
+/// Read a shared file for the conductor, reporting the failures to the user
+fn import(path: &Path, ui_tx: &Sender<thread_ui::Msg>, co_tx: &Sender<thread_conductor::Msg>) {
+    match SharedFile::load(path) {
+        Ok(file) => {
+            log::info!("Importing {}", path.display());
+            co_tx
+                .send(thread_conductor::Msg::Imported(Box::new(file)))
+                .ok();
+        }
+        Err(e) => {
+            log::error!("Failed to import {}: {:#}", path.display(), e);
+            ui_tx
+                .send(thread_ui::Msg::StorageError(format!(
+                    "Failed to import {}: {e}",
+                    path.display()
+                )))
+                .ok();
+        }
+    }
+}
+
+/// Read a Scala file for the conductor, reporting the failures to the user
+fn load_tuning(path: &Path, ui_tx: &Sender<thread_ui::Msg>, co_tx: &Sender<thread_conductor::Msg>) {
+    match TuningFile::load(path) {
+        Ok(file) => {
+            log::info!("Loading the tuning {}", path.display());
+            co_tx.send(thread_conductor::Msg::TuningLoaded(file)).ok();
+        }
+        Err(e) => {
+            log::error!("Failed to load the tuning {}: {:#}", path.display(), e);
+            ui_tx
+                .send(thread_ui::Msg::StorageError(format!(
+                    "Failed to load the tuning {}: {e}",
+                    path.display()
+                )))
+                .ok();
+        }
+    }
+}
+
+/// Write the settings, reporting the failures to the user
+fn save(settings: &Settings, path: &Path, ui_tx: &Sender<thread_ui::Msg>) {
+    if let Err(e) = settings.save_to(path) {
+        log::error!("Failed to save the settings: {:#}", e);
+        ui_tx
+            .send(thread_ui::Msg::StorageError(format!(
+                "Failed to save the settings: {e}"
+            )))
+            .ok();
+    }
+}
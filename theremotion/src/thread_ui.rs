@@ -1,4 +1,4 @@
-use std::{collections::HashSet, rc::Rc};
+use std::{cell::RefCell, collections::HashSet, rc::Rc};
 
 use itertools::Itertools;
 use nalgebra::Vector2;
@@ -8,12 +8,32 @@ use std::sync::mpsc::{Receiver, Sender};
 use theremotion_ui::MainWindow;
 
 use crate::{
-    controls::Controls,
-    settings::{Handedness, Settings},
+    controls::{Controls, EnvelopeControl},
+    looper::{LooperCommand, LooperState, LooperStatus},
+    morph::MorphMode,
+    settings::{EnvelopeSettings, GestureAction, Handedness, Scene, Settings, USER_SCALE_CATEGORY},
+    solfege::{scale_catalog, ArpPattern},
+    tempo::TempoSync,
     thread_conductor::{Msg as CM, TrackingStatus},
+    vibrato::VibratoGesture,
     {MidiNoteF, Volume},
 };
 
+/// Scale category showing all the scales
+const ALL_SCALE_CATEGORIES: &str = "All";
+
+/// Number of octaves of the keyboard
+const KEYBOARD_OCTAVES: usize = 4;
+
+/// Center of the keys of an octave, in white key widths, followed by the
+/// next octave
+const KEY_CENTERS: [f32; 13] = [
+    0.5, 1.0, 1.5, 2.0, 2.5, 3.5, 4.0, 4.5, 5.0, 5.5, 6.0, 6.5, 7.5,
+];
+
+/// Largest distance of a tuned note to a key to be shown on it, in semitones
+const KEY_TOLERANCE: f32 = 0.01;
+
 /// Message to update externally the UI
 #[derive(Debug)]
 pub enum Msg {
@@ -24,7 +44,7 @@ pub enum Msg {
     /// Lead instrument volume (0-1)
     LeadVolume(f32),
     /// Lead notes, volume and raw horizontal coordinates
-    Lead([(MidiNoteF, Volume); 4], Vector2<f32>),
+    Lead(Vec<(MidiNoteF, Volume)>, Vector2<f32>),
     /// Floating number of chord notes (2.5 is 2 chord notes and the next half volume)
     ChordsNumber(f32),
     /// Floating number of drone notes
@@ -42,15 +62,21 @@ pub enum Msg {
     StrumReady(bool),
     /// Trumpet string strength (0-1)
     TrumpetStrength(f32),
+    /// Looper state
+    Looper(LooperStatus),
     /// Settings update from leap
     Settings(Settings),
+    /// Settings could not be read or written
+    StorageError(String),
+    /// Tempo followed from the MIDI clock
+    Tempo(f32),
 }
 
 pub fn run(
     tx: Sender<CM>,
     mut ui_rx: Receiver<Msg>,
     controls: Controls,
-    mut settings: Settings,
+    settings: Settings,
 ) -> (MainWindow, slint::Timer) {
     if settings.system.fullscreen {
         std::env::set_var("SLINT_FULLSCREEN", "1");
@@ -61,7 +87,19 @@ pub fn run(
     // Send the min/max set in the DSP
     set_ui_controls(&window.global::<theremotion_ui::UIState<'_>>(), controls);
 
-    update_ui_from_settings(&window.global::<theremotion_ui::UIState<'_>>(), &settings);
+    let categories = [ALL_SCALE_CATEGORIES, USER_SCALE_CATEGORY]
+        .into_iter()
+        .chain(scale_catalog().iter().map(|c| c.name.as_str()))
+        .map(SharedString::from)
+        .collect_vec();
+    let ui = window.global::<theremotion_ui::UIState<'_>>();
+    ui.set_scale_categories(ModelRc::from(Rc::new(VecModel::from(categories))));
+    ui.set_scale_category(ALL_SCALE_CATEGORIES.into());
+
+    update_ui_from_settings(&ui, &settings);
+
+    // Shared with the scale filter, updated locally
+    let settings = Rc::new(RefCell::new(settings));
 
     /// Helper to connect slint callbacks to events sent on a channel
     struct Connector(Sender<CM>);
@@ -112,6 +150,8 @@ pub fn run(
 
     // Common
     ui.on_close(c.send2(|| CM::Exit));
+    ui.on_undo(c.send2(|| CM::Undo));
+    ui.on_redo(c.send2(|| CM::Redo));
 
     // Play tab
     ui.on_drone_clicked(c.send(CM::DroneClicked));
@@ -121,8 +161,25 @@ pub fn run(
     ui.on_lead_octave_clicked(c.send(CM::LeadOctave));
     ui.on_guitar_octave_clicked(c.send(CM::GuitarOctave));
     ui.on_drone_octave_clicked(c.send(CM::DroneOctave));
+    ui.on_reference_pitch_changed(c.send(CM::ReferencePitch));
 
     // Scale tab
+    ui.on_load_tuning(c.send(CM::LoadTuning));
+    ui.on_reset_tuning(c.send2(|| CM::ResetTuning));
+    ui.on_edo_clicked(c.send(CM::Edo));
+    ui.on_edo_degree_clicked(c.send(CM::EdoDegreeClicked));
+    ui.on_export_scale(c.send(CM::ExportScale));
+    ui.on_scale_filter_changed({
+        let window_weak = window_weak.clone();
+        let settings = settings.clone();
+        move || {
+            let window = window_weak.unwrap();
+            update_scale_list(
+                &window.global::<theremotion_ui::UIState<'_>>(),
+                &settings.borrow(),
+            );
+        }
+    });
     ui.on_scale_clicked(c.send(CM::ScaleClicked));
     ui.on_select_scale(c.send(CM::SelectScale));
     ui.on_delete_scale(c.send(CM::DeleteScale));
@@ -135,6 +192,22 @@ pub fn run(
     ui.on_mix_master_changed(c.send(CM::MasterVolume));
     ui.on_guitar_drone_clicked(c.send2(|| CM::GuitarDroneClicked));
 
+    // Sound tab
+    ui.on_wave_sine_changed(c.send(CM::WaveSine));
+    ui.on_wave_triangle_changed(c.send(CM::WaveTriangle));
+    ui.on_wave_square_changed(c.send(CM::WaveSquare));
+    ui.on_wave_pulse_width_changed(c.send(CM::WavePulseWidth));
+    ui.on_wave_saw_changed(c.send(CM::WaveSaw));
+    ui.on_wave_theremin_changed(c.send(CM::WaveTheremin));
+    ui.on_vibrato_rate_changed(c.send(CM::VibratoRate));
+    ui.on_vibrato_depth_changed(c.send(CM::VibratoDepth));
+    ui.on_vibrato_onset_changed(c.send(CM::VibratoOnset));
+    ui.on_vibrato_gesture_clicked(c.send(CM::VibratoGesture));
+    ui.on_glide_time_changed(c.send(CM::GlideTime));
+    ui.on_legato_clicked(c.send2(|| CM::LegatoClicked));
+    ui.on_lead_envelope_changed(c.send(CM::LeadEnvelope));
+    ui.on_drone_envelope_changed(c.send(CM::DroneEnvelope));
+
     // Effects tab
     ui.on_echo_mix_changed(c.send(CM::EchoAmount));
     ui.on_echo_duration_changed(c.send(CM::EchoDuration));
@@ -143,12 +216,55 @@ pub fn run(
     ui.on_reverb_time_changed(c.send(CM::ReverbTime));
     ui.on_reverb_damp_changed(c.send(CM::ReverbDamp));
     ui.on_reverb_size_changed(c.send(CM::ReverbSize));
+    ui.on_distortion_mix_changed(c.send(CM::DistortionAmount));
+    ui.on_distortion_drive_changed(c.send(CM::DistortionDrive));
+    ui.on_eq_low_changed(c.send(CM::EqLow));
+    ui.on_eq_mid_changed(c.send(CM::EqMid));
+    ui.on_eq_high_changed(c.send(CM::EqHigh));
+    ui.on_chorus_mix_changed(c.send(CM::ChorusAmount));
+    ui.on_chorus_rate_changed(c.send(CM::ChorusRate));
+    ui.on_chorus_depth_changed(c.send(CM::ChorusDepth));
+    ui.on_phaser_mix_changed(c.send(CM::PhaserAmount));
+    ui.on_phaser_rate_changed(c.send(CM::PhaserRate));
+    ui.on_phaser_feedback_changed(c.send(CM::PhaserFeedback));
     ui.on_drone_detune_changed(c.send(CM::DroneDetune));
 
+    // Advanced tab
+    ui.on_parameter_changed({
+        let tx = tx.clone();
+        move |path, value| tx.send(CM::SetParameter(path.into(), value)).unwrap()
+    });
+
+    // Rhythm tab
+    ui.on_tempo_changed(c.send(CM::Tempo));
+    ui.on_tap_tempo(c.send2(|| CM::TapTempo));
+    ui.on_echo_sync_clicked(c.send(CM::EchoSync));
+    ui.on_strum_spacing_changed(c.send(CM::StrumSpacing));
+    ui.on_strum_quantize_clicked(c.send(CM::StrumQuantize));
+    ui.on_looper_record(c.send2(|| CM::Looper(LooperCommand::Record)));
+    ui.on_looper_play_stop(c.send2(|| CM::Looper(LooperCommand::PlayStop)));
+    ui.on_looper_undo(c.send2(|| CM::Looper(LooperCommand::Undo)));
+    ui.on_looper_clear(c.send2(|| CM::Looper(LooperCommand::Clear)));
+    ui.on_arp_pattern_clicked(c.send(CM::ArpPattern));
+    ui.on_arp_division_clicked(c.send(CM::ArpDivision));
+    ui.on_arp_gate_changed(c.send(CM::ArpGate));
+
     // Presets tab
     ui.on_select_preset(c.send(CM::SelectPreset));
     ui.on_delete_preset(c.send(CM::DeletePreset));
     ui.on_save_preset(c.send(CM::SavePreset));
+    ui.on_export_preset(c.send(CM::ExportPreset));
+    ui.on_export_bundle(c.send(CM::ExportBundle));
+    ui.on_import_file(c.send(CM::Import));
+    ui.on_morph_mode_clicked(c.send(CM::MorphMode));
+    ui.on_morph_duration_changed(c.send(CM::MorphDuration));
+    ui.on_morph_switch_changed(c.send(CM::MorphSwitch));
+    ui.on_next_scene(c.send2(|| CM::NextScene));
+    ui.on_previous_scene(c.send2(|| CM::PreviousScene));
+    ui.on_select_scene(c.send(CM::SelectScene));
+    ui.on_add_scene(c.send2(|| CM::AddScene));
+    ui.on_remove_scene(c.send(CM::RemoveScene));
+    ui.on_scene_notes_edited(c.send(CM::SceneNotes));
 
     // Settings tab
     ui.on_fullscreen_clicked(c.send2(|| CM::FullscreenClicked));
@@ -156,6 +272,7 @@ pub fn run(
     ui.on_on_screen_kbd_clicked(c.send2(|| CM::OnScreenKeyboardClicked));
     ui.on_lh_clicked(c.send2(|| CM::LHClicked));
     ui.on_rh_clicked(c.send2(|| CM::RHClicked));
+    ui.on_fist_action_clicked(c.send(CM::FistAction));
     ui.on_high_priority_clicked(c.send2(|| CM::HighPriorityClicked));
 
     let window_timer = slint::Timer::default();
@@ -166,7 +283,7 @@ pub fn run(
         {
             move || {
                 let window = window_weak.unwrap();
-                read_updates(&mut ui_rx, &mut settings, &window);
+                read_updates(&mut ui_rx, &mut settings.borrow_mut(), &window);
             }
         },
     );
@@ -203,27 +320,41 @@ fn read_updates(
             Msg::Lead(notes, coords) => {
                 let coords_direction = coords.normalize();
                 let range_end = *settings.current_preset.note_range_f().end();
-                ui.set_tuner_note_tuned(notes[0].0 .0);
+                if let Some((note, _)) = notes.first() {
+                    ui.set_tuner_note_tuned(note.0);
+                }
                 // Lead for dots
-                ui.set_notes(
+                let note_points = VecModel::from(
                     notes
+                        .iter()
                         .map(|(note, volume)| {
-                            let coords = coords_direction * (range_end - note).semitones();
+                            let coords = coords_direction * (range_end - *note).semitones();
                             theremotion_ui::NotePoint {
                                 volume: volume.0,
                                 x: coords.x,
                                 y: coords.y,
                             }
                         })
-                        .into(),
+                        .collect_vec(),
                 );
+                ui.set_notes(ModelRc::from(Rc::new(note_points)));
                 ui.set_raw_note(theremotion_ui::NotePoint {
                     volume: 1.0,
                     x: coords.x,
                     y: coords.y,
                 });
                 // Lead for keyboard
-                let mut leads = [0.0; 12 * 4];
+                let lead_pitches = VecModel::from(
+                    notes
+                        .iter()
+                        .map(|(note, volume)| theremotion_ui::PitchMark {
+                            position: keyboard_position(*note),
+                            volume: volume.0,
+                        })
+                        .collect_vec(),
+                );
+                ui.set_lead_pitches(ModelRc::from(Rc::new(lead_pitches)));
+                let mut leads = [0.0; 12 * KEYBOARD_OCTAVES];
                 for (note, volume) in notes {
                     let index_1 = note.0.floor();
                     let index_1_factor = 1.0 - (note.0 - index_1);
@@ -263,16 +394,41 @@ fn read_updates(
             }
             Msg::StrumReady(s) => ui.set_strum_ready(s),
             Msg::TrumpetStrength(_) => {} // todo?
+            Msg::Looper(status) => {
+                ui.set_looper_state(status.state.into());
+                ui.set_looper_layers(status.layers.try_into().unwrap_or_default());
+            }
             Msg::Settings(s) => {
                 *settings = s;
                 update_ui_from_settings(&ui, settings);
             }
+            Msg::StorageError(e) => ui.set_storage_error(e.into()),
+            Msg::Tempo(t) => ui.set_tempo(t),
         }
     }
 }
 
 fn set_ui_controls(ui: &theremotion_ui::UIState<'_>, controls: Controls) {
+    ui.set_voices(controls.lead.len() as i32);
+    // Offset by one, 1 note and half being heard like two notes
+    let graduations = (1..controls.lead.len()).map(|value| theremotion_ui::Graduation {
+        value: value as f32,
+        text: (value + 1).to_string().into(),
+    });
+    ui.set_chord_graduations(ModelRc::from(Rc::new(VecModel::from_iter(graduations))));
     ui.set_drone_detune_control(ui_control(&controls.drone_detune));
+    ui.set_wave_sine_control(ui_control(&controls.wave_sine));
+    ui.set_wave_triangle_control(ui_control(&controls.wave_triangle));
+    ui.set_wave_square_control(ui_control(&controls.wave_square));
+    ui.set_wave_pulse_width_control(ui_control(&controls.wave_pulse_width));
+    ui.set_wave_saw_control(ui_control(&controls.wave_saw));
+    ui.set_wave_theremin_control(ui_control(&controls.wave_theremin));
+    ui.set_vibrato_rate_control(ui_control(&controls.vibrato_rate));
+    ui.set_vibrato_depth_control(ui_control(&controls.vibrato_depth));
+    ui.set_vibrato_onset_control(ui_control(&controls.vibrato_onset));
+    ui.set_glide_time_control(ui_control(&controls.glide_time));
+    ui.set_lead_envelope_control(envelope_control(&controls.lead_envelope));
+    ui.set_drone_envelope_control(envelope_control(&controls.drone_envelope));
     ui.set_echo_mix_control(ui_control(&controls.echo_mix));
     ui.set_echo_duration_control(ui_control(&controls.echo_duration));
     ui.set_echo_feedback_control(ui_control(&controls.echo_feedback));
@@ -280,10 +436,36 @@ fn set_ui_controls(ui: &theremotion_ui::UIState<'_>, controls: Controls) {
     ui.set_reverb_time_control(ui_control(&controls.reverb_time));
     ui.set_reverb_damp_control(ui_control(&controls.reverb_damp));
     ui.set_reverb_size_control(ui_control(&controls.reverb_size));
+    ui.set_distortion_mix_control(ui_control(&controls.distortion_mix));
+    ui.set_distortion_drive_control(ui_control(&controls.distortion_drive));
+    ui.set_eq_low_control(ui_control(&controls.eq_low));
+    ui.set_eq_mid_control(ui_control(&controls.eq_mid));
+    ui.set_eq_high_control(ui_control(&controls.eq_high));
+    ui.set_chorus_mix_control(ui_control(&controls.chorus_mix));
+    ui.set_chorus_rate_control(ui_control(&controls.chorus_rate));
+    ui.set_chorus_depth_control(ui_control(&controls.chorus_depth));
+    ui.set_phaser_mix_control(ui_control(&controls.phaser_mix));
+    ui.set_phaser_rate_control(ui_control(&controls.phaser_rate));
+    ui.set_phaser_feedback_control(ui_control(&controls.phaser_feedback));
     ui.set_mix_master_control(ui_control(&controls.mix_master_volume));
     ui.set_mix_drone_control(ui_control(&controls.mix_drone_volume));
     ui.set_mix_lead_control(ui_control(&controls.mix_lead_volume));
     ui.set_mix_pluck_control(ui_control(&controls.mix_pluck_volume));
+
+    let parameters = VecModel::from(
+        controls
+            .parameters
+            .iter()
+            .map(|control| theremotion_ui::DspParameter {
+                path: control.path.clone().into(),
+                control: ui_control(control),
+                init: control.input.init,
+                value: control.input.init,
+                text: format_value(control.input.init, control.input.step).into(),
+            })
+            .collect_vec(),
+    );
+    ui.set_parameters(ModelRc::from(Rc::new(parameters)));
 }
 
 fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings) {
@@ -291,6 +473,10 @@ fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings
     ui.set_fullscreen(settings.system.fullscreen);
     ui.set_high_priority(settings.system.high_priority_process);
     ui.set_use_on_screen_keyboard(settings.system.force_touchscreen);
+    ui.set_fist_action(settings.system.fist_action.into());
+    ui.set_morph_mode(settings.morph.mode.into());
+    ui.set_morph_duration(settings.morph.duration);
+    ui.set_morph_switch(settings.morph.switch_at);
 
     let preset = &settings.current_preset;
     ui.set_lead_octave(preset.lead_octave.into_i8() as i32);
@@ -306,28 +492,91 @@ fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings
     ui.set_enable_guitar_drone(preset.drone.pluck_drone);
 
     ui.set_echo_mix(preset.fx.echo.mix.0);
-    ui.set_echo_duration(preset.fx.echo.duration);
+    ui.set_echo_duration(preset.fx.echo.effective_duration(preset.tempo));
     ui.set_echo_feedback(preset.fx.echo.feedback);
     ui.set_reverb_mix(preset.fx.reverb.mix.0);
     ui.set_reverb_time(preset.fx.reverb.time);
     ui.set_reverb_damp(preset.fx.reverb.damp);
     ui.set_reverb_size(preset.fx.reverb.size);
+    ui.set_distortion_mix(preset.fx.distortion.mix.0);
+    ui.set_distortion_drive(preset.fx.distortion.drive);
+    ui.set_eq_low(preset.fx.eq.low);
+    ui.set_eq_mid(preset.fx.eq.mid);
+    ui.set_eq_high(preset.fx.eq.high);
+    ui.set_chorus_mix(preset.fx.chorus.mix.0);
+    ui.set_chorus_rate(preset.fx.chorus.rate);
+    ui.set_chorus_depth(preset.fx.chorus.depth);
+    ui.set_phaser_mix(preset.fx.phaser.mix.0);
+    ui.set_phaser_rate(preset.fx.phaser.rate);
+    ui.set_phaser_feedback(preset.fx.phaser.feedback);
     ui.set_drone_detune(preset.drone.detune);
-
-    let root_pitch = settings.current_preset.pitch;
-    ui.set_root_pitch(root_pitch.into_byte().into());
-    let scales = VecModel::from(
-        settings
-            .system_and_user_scales()
-            .map(|(scale, user)| theremotion_ui::Selectable {
-                id: scale.id(),
-                selected: scale.scale == preset.scale,
-                name: scale.name.into(),
-                removable: user,
+    ui.set_wave_sine(preset.wave.sine);
+    ui.set_wave_triangle(preset.wave.triangle);
+    ui.set_wave_square(preset.wave.square);
+    ui.set_wave_pulse_width(preset.wave.pulse_width);
+    ui.set_wave_saw(preset.wave.saw);
+    ui.set_wave_theremin(preset.wave.theremin);
+    ui.set_vibrato_rate(preset.vibrato.rate);
+    ui.set_vibrato_depth(preset.vibrato.depth);
+    ui.set_vibrato_onset(preset.vibrato.onset);
+    ui.set_vibrato_gesture(preset.vibrato.gesture.into());
+    ui.set_glide_time(preset.glide.time);
+    ui.set_legato(preset.glide.legato);
+    ui.set_lead_envelope((&preset.lead_envelope).into());
+    ui.set_drone_envelope((&preset.drone_envelope).into());
+    let parameters = VecModel::from(
+        ui.get_parameters()
+            .iter()
+            .map(|parameter| {
+                let value = preset
+                    .parameters
+                    .get(parameter.path.as_str())
+                    .copied()
+                    .unwrap_or(parameter.init);
+                theremotion_ui::DspParameter {
+                    value,
+                    text: format_value(value, parameter.control.step).into(),
+                    ..parameter
+                }
             })
             .collect_vec(),
     );
-    ui.set_scale_presets(ModelRc::from(Rc::new(scales)));
+    ui.set_parameters(ModelRc::from(Rc::new(parameters)));
+
+    ui.set_tempo(preset.tempo);
+    ui.set_echo_sync(preset.fx.echo.sync.into());
+    ui.set_strum_spacing(preset.strum.spacing);
+    ui.set_strum_quantize(preset.strum.quantize.into());
+    ui.set_arp_pattern(preset.arpeggiator.pattern.into());
+    ui.set_arp_division(preset.arpeggiator.division.into());
+    ui.set_arp_gate(preset.arpeggiator.gate);
+
+    let tuning = preset.active_tuning();
+    ui.set_tuning_name(
+        tuning
+            .as_ref()
+            .map(|t| t.description.clone())
+            .unwrap_or_default()
+            .into(),
+    );
+    ui.set_has_tuning(tuning.is_some());
+    ui.set_reference_pitch(preset.reference_pitch);
+    match &preset.edo {
+        Some(edo) => {
+            ui.set_edo_divisions(edo.divisions.into());
+            let degrees = VecModel::from(
+                (0..edo.divisions)
+                    .map(|degree| edo.contains(degree))
+                    .collect_vec(),
+            );
+            ui.set_edo_degrees(ModelRc::from(Rc::new(degrees)));
+        }
+        None => ui.set_edo_divisions(0),
+    }
+
+    let root_pitch = settings.current_preset.pitch;
+    ui.set_root_pitch(root_pitch.into_byte().into());
+    update_scale_list(ui, settings);
 
     let presets = VecModel::from(
         settings
@@ -342,11 +591,48 @@ fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings
     );
     ui.set_presets(ModelRc::from(Rc::new(presets)));
 
-    let scale: HashSet<MidiNote> = preset.restricted_scale().into_iter().collect();
+    let setlist = &settings.setlist;
+    let scenes = VecModel::from(
+        setlist
+            .scenes
+            .iter()
+            .enumerate()
+            .map(|(index, scene)| theremotion_ui::Selectable {
+                id: index as i32,
+                name: format!("{}. {}", index + 1, scene.preset).into(),
+                removable: true,
+                selected: index == setlist.current,
+            })
+            .collect_vec(),
+    );
+    ui.set_scenes(ModelRc::from(Rc::new(scenes)));
+    ui.set_scene_count(setlist.scenes.len() as i32);
+    let scene_name =
+        |scene: Option<&Scene>| scene.map(|s| s.preset.clone()).unwrap_or_default().into();
+    ui.set_scene_current(scene_name(setlist.current_scene()));
+    ui.set_scene_next(scene_name(setlist.next_scene()));
+    ui.set_scene_notes(
+        setlist
+            .current_scene()
+            .map(|s| s.notes.clone())
+            .unwrap_or_default()
+            .into(),
+    );
+
+    // Microtonal notes are shown between the keys
+    let scale_notes = preset.restricted_scale();
+    let scale: HashSet<i32> = scale_notes
+        .iter()
+        .filter(|note| (note.0 - note.0.round()).abs() < KEY_TOLERANCE)
+        .map(|note| note.round().note() as i32)
+        .collect();
     let ui_scale = ui.get_scale_notes();
-    for index in 0..(12 * 4) {
-        ui_scale.set_row_data(index, scale.contains(&MidiNote::from_byte(index as u8)));
+    for index in 0..(12 * KEYBOARD_OCTAVES) {
+        ui_scale.set_row_data(index, scale.contains(&(index as i32)));
     }
+    let scale_pitches =
+        VecModel::from(scale_notes.into_iter().map(keyboard_position).collect_vec());
+    ui.set_scale_pitches(ModelRc::from(Rc::new(scale_pitches)));
 
     let drone_notes: HashSet<MidiNote> = settings
         .current_preset
@@ -357,7 +643,7 @@ fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings
 
     let ui_drones = ui.get_drones();
 
-    for index in 0..=12 * 4 {
+    for index in 0..=(12 * KEYBOARD_OCTAVES) as u8 {
         let note = MidiNote::from(index);
         let index = index as usize;
         ui_drones.set_row_data(
@@ -371,6 +657,38 @@ fn update_ui_from_settings(ui: &theremotion_ui::UIState<'_>, settings: &Settings
     }
 }
 
+/// Position of a note on the keyboard (0-1), the microtonal notes being
+/// between the keys
+fn keyboard_position(note: MidiNoteF) -> f32 {
+    let octave = (note.0 / 12.0).floor();
+    let semitone = note.0 - octave * 12.0;
+    let key = (semitone.floor() as usize).min(11);
+    let center =
+        KEY_CENTERS[key] + (KEY_CENTERS[key + 1] - KEY_CENTERS[key]) * (semitone - key as f32);
+    (octave * 7.0 + center) / (7 * KEYBOARD_OCTAVES) as f32
+}
+
+/// Fill the scale list, filtered on the selected category and search
+fn update_scale_list(ui: &theremotion_ui::UIState<'_>, settings: &Settings) {
+    let category = ui.get_scale_category();
+    let search = ui.get_scale_search();
+    let scales = VecModel::from(
+        settings
+            .categorized_scales()
+            .filter(|(c, scale, _)| {
+                (category == ALL_SCALE_CATEGORIES || category == *c) && scale.matches(&search)
+            })
+            .map(|(_, scale, user)| theremotion_ui::Selectable {
+                id: scale.id(),
+                selected: scale.scale == settings.current_preset.scale,
+                name: scale.name.into(),
+                removable: user,
+            })
+            .collect_vec(),
+    );
+    ui.set_scale_presets(ModelRc::from(Rc::new(scales)));
+}
+
 impl From<theremotion_ui::Handedness> for Handedness {
     fn from(value: theremotion_ui::Handedness) -> Self {
         match value {
@@ -389,9 +707,201 @@ impl From<Handedness> for theremotion_ui::Handedness {
     }
 }
 
+impl From<theremotion_ui::ArpPattern> for ArpPattern {
+    fn from(value: theremotion_ui::ArpPattern) -> Self {
+        match value {
+            theremotion_ui::ArpPattern::Off => ArpPattern::Off,
+            theremotion_ui::ArpPattern::Up => ArpPattern::Up,
+            theremotion_ui::ArpPattern::Down => ArpPattern::Down,
+            theremotion_ui::ArpPattern::UpDown => ArpPattern::UpDown,
+            theremotion_ui::ArpPattern::Random => ArpPattern::Random,
+            theremotion_ui::ArpPattern::AsPlayed => ArpPattern::AsPlayed,
+        }
+    }
+}
+
+impl From<ArpPattern> for theremotion_ui::ArpPattern {
+    fn from(value: ArpPattern) -> Self {
+        match value {
+            ArpPattern::Off => theremotion_ui::ArpPattern::Off,
+            ArpPattern::Up => theremotion_ui::ArpPattern::Up,
+            ArpPattern::Down => theremotion_ui::ArpPattern::Down,
+            ArpPattern::UpDown => theremotion_ui::ArpPattern::UpDown,
+            ArpPattern::Random => theremotion_ui::ArpPattern::Random,
+            ArpPattern::AsPlayed => theremotion_ui::ArpPattern::AsPlayed,
+        }
+    }
+}
+
 fn ui_control(control: &crate::controls::Control) -> theremotion_ui::DspControl {
     theremotion_ui::DspControl {
         min: *control.input.range.start(),
         max: *control.input.range.end(),
+        step: control.input.step,
+        log: control.log,
+    }
+}
+
+fn envelope_control(control: &EnvelopeControl) -> theremotion_ui::EnvelopeControl {
+    theremotion_ui::EnvelopeControl {
+        attack: ui_control(&control.attack),
+        decay: ui_control(&control.decay),
+        sustain: ui_control(&control.sustain),
+        release: ui_control(&control.release),
+    }
+}
+
+/// Display a value with the precision of its step
+fn format_value(value: f32, step: f32) -> String {
+    let decimals = if step > 0.0 {
+        (-step.log10().floor()).max(0.0) as usize
+    } else {
+        3
+    };
+    format!("{value:.decimals$}")
+}
+
+impl From<theremotion_ui::TempoSync> for TempoSync {
+    fn from(value: theremotion_ui::TempoSync) -> Self {
+        match value {
+            theremotion_ui::TempoSync::Free => TempoSync::Free,
+            theremotion_ui::TempoSync::Half => TempoSync::Half,
+            theremotion_ui::TempoSync::Quarter => TempoSync::Quarter,
+            theremotion_ui::TempoSync::DottedEighth => TempoSync::DottedEighth,
+            theremotion_ui::TempoSync::Eighth => TempoSync::Eighth,
+            theremotion_ui::TempoSync::QuarterTriplet => TempoSync::QuarterTriplet,
+            theremotion_ui::TempoSync::EighthTriplet => TempoSync::EighthTriplet,
+            theremotion_ui::TempoSync::Sixteenth => TempoSync::Sixteenth,
+        }
+    }
+}
+
+impl From<TempoSync> for theremotion_ui::TempoSync {
+    fn from(value: TempoSync) -> Self {
+        match value {
+            TempoSync::Free => theremotion_ui::TempoSync::Free,
+            TempoSync::Half => theremotion_ui::TempoSync::Half,
+            TempoSync::Quarter => theremotion_ui::TempoSync::Quarter,
+            TempoSync::DottedEighth => theremotion_ui::TempoSync::DottedEighth,
+            TempoSync::Eighth => theremotion_ui::TempoSync::Eighth,
+            TempoSync::QuarterTriplet => theremotion_ui::TempoSync::QuarterTriplet,
+            TempoSync::EighthTriplet => theremotion_ui::TempoSync::EighthTriplet,
+            TempoSync::Sixteenth => theremotion_ui::TempoSync::Sixteenth,
+        }
+    }
+}
+
+impl From<theremotion_ui::GestureAction> for GestureAction {
+    fn from(value: theremotion_ui::GestureAction) -> Self {
+        match value {
+            theremotion_ui::GestureAction::None => GestureAction::None,
+            theremotion_ui::GestureAction::TapTempo => GestureAction::TapTempo,
+            theremotion_ui::GestureAction::Looper => GestureAction::Looper,
+            theremotion_ui::GestureAction::NextScene => GestureAction::NextScene,
+        }
+    }
+}
+
+impl From<GestureAction> for theremotion_ui::GestureAction {
+    fn from(value: GestureAction) -> Self {
+        match value {
+            GestureAction::None => theremotion_ui::GestureAction::None,
+            GestureAction::TapTempo => theremotion_ui::GestureAction::TapTempo,
+            GestureAction::Looper => theremotion_ui::GestureAction::Looper,
+            GestureAction::NextScene => theremotion_ui::GestureAction::NextScene,
+        }
+    }
+}
+
+impl From<LooperState> for theremotion_ui::LooperState {
+    fn from(value: LooperState) -> Self {
+        match value {
+            LooperState::Empty => theremotion_ui::LooperState::Empty,
+            LooperState::Recording => theremotion_ui::LooperState::Recording,
+            LooperState::Playing => theremotion_ui::LooperState::Playing,
+            LooperState::Overdubbing => theremotion_ui::LooperState::Overdubbing,
+            LooperState::Stopped => theremotion_ui::LooperState::Stopped,
+        }
+    }
+}
+
+impl From<theremotion_ui::VibratoGesture> for VibratoGesture {
+    fn from(value: theremotion_ui::VibratoGesture) -> Self {
+        match value {
+            theremotion_ui::VibratoGesture::Always => VibratoGesture::Always,
+            theremotion_ui::VibratoGesture::Tremble => VibratoGesture::Tremble,
+            theremotion_ui::VibratoGesture::Tilt => VibratoGesture::Tilt,
+        }
+    }
+}
+
+impl From<VibratoGesture> for theremotion_ui::VibratoGesture {
+    fn from(value: VibratoGesture) -> Self {
+        match value {
+            VibratoGesture::Always => theremotion_ui::VibratoGesture::Always,
+            VibratoGesture::Tremble => theremotion_ui::VibratoGesture::Tremble,
+            VibratoGesture::Tilt => theremotion_ui::VibratoGesture::Tilt,
+        }
+    }
+}
+
+impl From<theremotion_ui::MorphMode> for MorphMode {
+    fn from(value: theremotion_ui::MorphMode) -> Self {
+        match value {
+            theremotion_ui::MorphMode::Off => MorphMode::Off,
+            theremotion_ui::MorphMode::Timed => MorphMode::Timed,
+            theremotion_ui::MorphMode::Gesture => MorphMode::Gesture,
+        }
+    }
+}
+
+impl From<MorphMode> for theremotion_ui::MorphMode {
+    fn from(value: MorphMode) -> Self {
+        match value {
+            MorphMode::Off => theremotion_ui::MorphMode::Off,
+            MorphMode::Timed => theremotion_ui::MorphMode::Timed,
+            MorphMode::Gesture => theremotion_ui::MorphMode::Gesture,
+        }
+    }
+}
+
+impl From<theremotion_ui::Envelope> for EnvelopeSettings {
+    fn from(value: theremotion_ui::Envelope) -> Self {
+        Self {
+            attack: value.attack,
+            decay: value.decay,
+            sustain: value.sustain,
+            release: value.release,
+        }
+    }
+}
+
+impl From<&EnvelopeSettings> for theremotion_ui::Envelope {
+    fn from(value: &EnvelopeSettings) -> Self {
+        Self {
+            attack: value.attack,
+            decay: value.decay,
+            sustain: value.sustain,
+            release: value.release,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case(0.0, 0.5)]
+    #[case(1.0, 1.0)]
+    #[case(4.5, 3.0)]
+    #[case(11.5, 7.0)]
+    #[case(12.0, 7.5)]
+    #[case(47.0, 27.5)]
+    fn keyboard_position_test(#[case] note: f32, #[case] white_keys: f32) {
+        let expected = white_keys / (7 * KEYBOARD_OCTAVES) as f32;
+        assert!((keyboard_position(MidiNoteF(note)) - expected).abs() < 0.0001);
     }
 }
@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Number of pitch hand speeds kept to measure the tremble
+const TREMBLE_WINDOW: usize = 20;
+
+/// Standard deviation of the pitch hand speed (mm/s) giving the full depth
+const TREMBLE_FULL: f32 = 150.0;
+
+/// Tilt of the volume hand (rad) giving the full depth
+const TILT_FULL: f32 = 0.6;
+
+/// Gesture controlling the vibrato depth
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VibratoGesture {
+    /// Full depth all the time
+    #[default]
+    Always,
+    /// Following the shaking of the pitch hand
+    Tremble,
+    /// Following the forward tilt of the volume hand
+    Tilt,
+}
+
+/// Hand movements driving the vibrato
+#[derive(Debug, Default)]
+pub struct Vibrato {
+    /// Last speeds of the pitch hand
+    speeds: VecDeque<f32>,
+    /// Last tilt of the volume hand
+    tilt: f32,
+}
+
+impl Vibrato {
+    /// Follow the speed of the pitch hand along the pitch axis
+    pub fn pitch_hand(&mut self, speed: f32) {
+        if self.speeds.len() == TREMBLE_WINDOW {
+            self.speeds.pop_front();
+        }
+        self.speeds.push_back(speed);
+    }
+
+    /// Follow the tilt of the volume hand
+    pub fn volume_hand(&mut self, tilt: f32) {
+        self.tilt = tilt;
+    }
+
+    /// Shaking of the pitch hand (0-1), from the variance of its speed.
+    ///
+    /// A steady movement is not a tremble, only its changes are.
+    pub fn tremble(&self) -> f32 {
+        if self.speeds.len() < 2 {
+            return 0.0;
+        }
+        let count = self.speeds.len() as f32;
+        let mean = self.speeds.iter().sum::<f32>() / count;
+        let variance = self.speeds.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / count;
+        (variance.sqrt() / TREMBLE_FULL).clamp(0.0, 1.0)
+    }
+
+    /// Portion (0-1) of the vibrato depth to play
+    pub fn amount(&self, gesture: VibratoGesture) -> f32 {
+        match gesture {
+            VibratoGesture::Always => 1.0,
+            VibratoGesture::Tremble => self.tremble(),
+            VibratoGesture::Tilt => (self.tilt / TILT_FULL).clamp(0.0, 1.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    fn tremble_test() {
+        let mut vibrato = Vibrato::default();
+        assert_eq!(0.0, vibrato.amount(VibratoGesture::Tremble));
+
+        // Moving steadily
+        for _ in 0..TREMBLE_WINDOW {
+            vibrato.pitch_hand(200.0);
+        }
+        assert_eq!(0.0, vibrato.amount(VibratoGesture::Tremble));
+        assert_eq!(1.0, vibrato.amount(VibratoGesture::Always));
+
+        // Shaking
+        for i in 0..TREMBLE_WINDOW {
+            vibrato.pitch_hand(if i % 2 == 0 { 75.0 } else { -75.0 });
+        }
+        assert!((vibrato.amount(VibratoGesture::Tremble) - 0.5).abs() < 0.001);
+    }
+
+    #[rstest]
+    #[case(-0.3, 0.0)]
+    #[case(0.3, 0.5)]
+    #[case(1.0, 1.0)]
+    fn tilt_test(#[case] tilt: f32, #[case] amount: f32) {
+        let mut vibrato = Vibrato::default();
+        vibrato.volume_hand(tilt);
+        assert!((vibrato.amount(VibratoGesture::Tilt) - amount).abs() < 0.001);
+    }
+}